@@ -0,0 +1,69 @@
+// the unit generated and original columns are counted in; the spec and browsers use
+// UTF-16 code units, some tools emit Unicode scalar values or UTF-8 bytes instead
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColumnUnit {
+    #[default]
+    Utf16,
+    Char,
+    Byte,
+}
+
+impl ColumnUnit {
+    pub fn char_width(self, c: char) -> usize {
+        match self {
+            ColumnUnit::Utf16 => c.len_utf16(),
+            ColumnUnit::Char => 1,
+            ColumnUnit::Byte => c.len_utf8(),
+        }
+    }
+
+    pub fn width(self, s: &str) -> usize {
+        match self {
+            ColumnUnit::Utf16 => s.encode_utf16().count(),
+            ColumnUnit::Char => s.chars().count(),
+            ColumnUnit::Byte => s.len(),
+        }
+    }
+
+    // byte offset of `column` in `line`, a column inside a character (e.g. between the
+    // two halves of a surrogate pair) is moved to the start of the next one
+    pub fn byte_offset(self, line: &str, column: usize) -> usize {
+        let mut current = 0;
+        for (offset, c) in line.char_indices() {
+            if current >= column {
+                return offset;
+            }
+            current += self.char_width(c);
+        }
+        line.len()
+    }
+
+    pub fn column_at(self, line: &str, byte_offset: usize) -> usize {
+        let mut column = 0;
+        for (offset, c) in line.char_indices() {
+            if offset >= byte_offset {
+                break;
+            }
+            column += self.char_width(c);
+        }
+        column
+    }
+}
+
+// converts `column` of `line` between units, columns past the end of the line are kept
+// at the same distance from it
+pub fn convert_column(line: &str, column: usize, from: ColumnUnit, to: ColumnUnit) -> usize {
+    if from == to {
+        return column;
+    }
+    let mut from_column = 0;
+    let mut to_column = 0;
+    for c in line.chars() {
+        if from_column >= column {
+            return to_column;
+        }
+        from_column += from.char_width(c);
+        to_column += to.char_width(c);
+    }
+    to_column + column.saturating_sub(from_column)
+}
@@ -0,0 +1,53 @@
+use error::Error;
+use regex::Regex;
+use source_map::SrcMap;
+use std::fmt::Write;
+
+// FNV-1a, 128-bit variant
+const FNV_OFFSET_BASIS: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+const FNV_PRIME: u128 = 0x0000_0000_0100_0000_0000_0000_0000_013b;
+
+// derives a UUID formatted debug ID from the generated code and its map, so that
+// rebuilding the same output yields the same ID; the map's own `debugId` is ignored
+pub fn generate_debug_id(code: &str, map: &SrcMap) -> Result<String, Error> {
+    let mut map = map.clone();
+    map.debug_id = None;
+    let json = map.to_json()?;
+
+    let mut hash = FNV_OFFSET_BASIS;
+    for byte in code.bytes().chain(Some(0)).chain(json.bytes()) {
+        hash ^= u128::from(byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+
+    let mut bytes = hash.to_be_bytes();
+    // version 4 and RFC 4122 variant bits
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let mut id = String::with_capacity(36);
+    for (i, byte) in bytes.iter().enumerate() {
+        if i == 4 || i == 6 || i == 8 || i == 10 {
+            id.push('-');
+        }
+        write!(id, "{:02x}", byte).unwrap();
+    }
+    Ok(id)
+}
+
+pub fn debug_id_comment(debug_id: &str) -> String {
+    format!("//# debugId={}", debug_id)
+}
+
+// returns the ID of the last `//# debugId=` comment in generated code
+pub fn parse_debug_id(code: &str) -> Option<String> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(
+            r"(?m)^[ \t]*(?://[#@][ \t]*debugId=([0-9a-fA-F-]+)|/\*[#@][ \t]*debugId=([0-9a-fA-F-]+)[ \t]*\*/)[ \t]*\r?$"
+        ).unwrap();
+    }
+    RE.captures_iter(code)
+        .last()
+        .and_then(|caps| caps.get(1).or_else(|| caps.get(2)))
+        .map(|m| m.as_str().to_lowercase())
+}
@@ -0,0 +1,86 @@
+use mapping::Mapping;
+use serde_json;
+use std::error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    Json(serde_json::Error),
+    Io(io::Error),
+    // byte offset of the offending segment in `mappings`
+    InvalidVlq(usize),
+    // byte offset in `rangeMappings`
+    InvalidRangeMappings(usize),
+    // byte offset in an `originalScopes` entry or `generatedRanges`
+    InvalidScopes(usize),
+    SourceOutOfRange(usize),
+    NameOutOfRange(usize),
+    InvalidMapping(Mapping),
+    UnsupportedVersion(i32),
+    MissingSourceFile,
+    InvalidDataUrl,
+    UnsupportedUrl(String),
+    // (line, column) of an index map section starting on line 0 or overlapping another
+    InvalidSectionOffset(usize, usize),
+    // `start..end` of an edit that is out of bounds, not on a character boundary, or
+    // moved inside itself
+    InvalidRange(usize, usize),
+    // index that would split an already overwritten or removed region
+    EditedRange(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Json(ref err) => write!(f, "invalid source map json: {}", err),
+            Error::Io(ref err) => write!(f, "failed to read source map: {}", err),
+            Error::InvalidVlq(offset) => write!(f, "invalid VLQ mapping at offset {}", offset),
+            Error::InvalidRangeMappings(offset) => {
+                write!(f, "invalid range mappings at offset {}", offset)
+            }
+            Error::InvalidScopes(offset) => write!(f, "invalid scopes at offset {}", offset),
+            Error::SourceOutOfRange(idx) => write!(f, "source index {} is out of range", idx),
+            Error::NameOutOfRange(idx) => write!(f, "name index {} is out of range", idx),
+            Error::InvalidMapping(ref mapping) => write!(f, "invalid mapping: {:?}", mapping),
+            Error::UnsupportedVersion(version) => {
+                write!(f, "unsupported source map version {}", version)
+            }
+            Error::MissingSourceFile => write!(
+                f,
+                "either an explicit source file or the source map's file property is required"
+            ),
+            Error::InvalidDataUrl => write!(f, "invalid source map data URL"),
+            Error::UnsupportedUrl(ref url) => write!(f, "cannot load source map from {}", url),
+            Error::InvalidSectionOffset(line, column) => {
+                write!(f, "invalid section offset {}:{}", line, column)
+            }
+            Error::InvalidRange(start, end) => write!(f, "invalid range {}..{}", start, end),
+            Error::EditedRange(idx) => {
+                write!(f, "cannot split an already edited region at {}", idx)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Json(ref err) => Some(err),
+            Error::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::Json(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
@@ -1,76 +1,109 @@
-use serde_json;
-use source_map_mappings::{parse_mappings, Mappings as _Mappings};
+use error::Error;
 use source_map_generator::SourceMapGenerator;
-use source_map::SrcMap;
+use source_map::SrcMapRef;
 use mapping::Mapping;
+use scopes;
 use std::collections::HashSet;
-use linked_hash_map::LinkedHashMap;
-use std::rc::Rc;
-use std::hash::Hash;
+use utils;
+use Ptr;
 use StringPtr;
 
-pub fn from_source_map(source_map: &str, check_dup: bool) -> SourceMapGenerator {
-    let source_map: SrcMap = serde_json::from_str(source_map).unwrap();
-    let file = source_map.file.map(|s| StringPtr::Str(s));
-    let source_root = source_map.source_root.map(|s| StringPtr::Str(s));
+pub fn from_source_map(source_map: &str, check_dup: bool) -> Result<SourceMapGenerator, Error> {
+    // the generator takes owned strings, so borrowing only spares `mappings`, which is decoded in place
+    let source_map = SrcMapRef::from_json(source_map)?;
+    let mut ignored = Vec::new();
+    for &idx in source_map.ignored_indices() {
+        let source = source_map.sources.get(idx).ok_or(Error::SourceOutOfRange(idx))?;
+        ignored.push(StringPtr::Str(source.to_string()));
+    }
+    let source_strs: Vec<&str> = source_map.sources.iter().map(|s| &**s).collect();
+    let name_strs: Vec<&str> = source_map.names.iter().map(|s| &**s).collect();
+    let mut original_scopes = Vec::new();
+    for (source, encoded) in source_strs.iter().zip(source_map.original_scopes.iter()) {
+        original_scopes.push((source.to_string(), scopes::decode_original_scopes(encoded, &name_strs)?));
+    }
+    let generated_ranges = match source_map.generated_ranges {
+        Some(ref encoded) => scopes::decode_generated_ranges(encoded, &source_strs, &name_strs)?,
+        None => Vec::new(),
+    };
+
+    let file = source_map.file.map(|s| StringPtr::Str(s.into_owned()));
+    let source_root = source_map.source_root.map(|s| StringPtr::Str(s.into_owned()));
 
     let mut generator = SourceMapGenerator::new(file, source_root, true);
+    generator.set_debug_id(source_map.debug_id.map(|s| StringPtr::Str(s.into_owned())));
+
+    for source in ignored {
+        generator.set_source_ignored(source, true);
+    }
+    for (source, scopes) in original_scopes {
+        if !scopes.is_empty() {
+            generator.set_original_scopes(StringPtr::Str(source), scopes);
+        }
+    }
+    for range in generated_ranges {
+        generator.add_generated_range(range);
+    }
 
-    let mut contents = source_map.sources_content.into_iter().map(|s| StringPtr::Ptr(Rc::new(s)));
-    let sources: Vec<Rc<String>> = if check_dup {
-        let mut set: HashSet<Rc<String>> = HashSet::new();
-        source_map.sources.into_iter().map(|s| Rc::new(s)).filter(|sp| {
-            generator.set_source_content(StringPtr::Ptr(sp.clone()), contents.next());
+    let mut contents = source_map
+        .sources_content
+        .into_iter()
+        .map(|content| content.map(|s| StringPtr::Str(s.into_owned())));
+    let sources: Vec<Ptr<String>> = if check_dup {
+        let mut set: HashSet<Ptr<String>> = HashSet::new();
+        source_map.sources.into_iter().map(|s| Ptr::new(s.into_owned())).filter(|sp| {
+            generator.set_source_content(StringPtr::Ptr(sp.clone()), contents.next().and_then(|content| content));
             set.insert(sp.clone())
         }).collect()
     } else {
         source_map.sources.into_iter().map(|s| {
-            let sp = Rc::new(s);
-            generator.set_source_content(StringPtr::Ptr(sp.clone()), contents.next());
+            let sp = Ptr::new(s.into_owned());
+            generator.set_source_content(StringPtr::Ptr(sp.clone()), contents.next().and_then(|content| content));
             sp
         }).collect()
     };
-    let names: Vec<Rc<String>> = if check_dup {
-        let mut set: HashSet<Rc<String>> = HashSet::new();
-        source_map.names.into_iter().map(|s| Rc::new(s)).filter(|sp|
+    let names: Vec<Ptr<String>> = if check_dup {
+        let mut set: HashSet<Ptr<String>> = HashSet::new();
+        source_map.names.into_iter().map(|s| Ptr::new(s.into_owned())).filter(|sp|
             set.insert(sp.clone())
         ).collect()
     } else {
-        source_map.names.into_iter().map(|s| Rc::new(s)).collect()
+        source_map.names.into_iter().map(|s| Ptr::new(s.into_owned())).collect()
     };
 
-    let mappings: _Mappings<()> = parse_mappings(source_map.mappings.as_bytes()).unwrap();
+    let mappings = utils::parse_mappings(&source_map.mappings)?;
     let mappings = mappings.by_generated_location();
+    let range_segments = match source_map.range_mappings {
+        Some(ref range_mappings) => {
+            utils::range_segments(&source_map.mappings, &utils::parse_range_mappings(range_mappings)?)?
+        }
+        None => HashSet::new(),
+    };
 
     for mapping in mappings {
-        let generated = (mapping.generated_line as usize, mapping.generated_column as usize);
+        let line = mapping.generated_line as usize;
+        // lines are stored 0-based in SourceMap spec version 3
+        let generated = (line + 1, mapping.generated_column as usize);
         let (original, source, name) = if let Some(original) = mapping.original.clone() {
-            let name = original.name.map(|idx| names[idx as usize].clone());
-            let source = sources[original.source as usize].clone();
-            (Some((original.original_line as usize, original.original_column as usize)), Some(source), name)
+            let name = match original.name {
+                Some(idx) => Some(names.get(idx as usize).ok_or(Error::NameOutOfRange(idx as usize))?.clone()),
+                None => None,
+            };
+            let source = sources
+                .get(original.source as usize)
+                .ok_or(Error::SourceOutOfRange(original.source as usize))?
+                .clone();
+            (Some((original.original_line as usize + 1, original.original_column as usize)), Some(source), name)
         } else {
             (None, None, None)
         };
-        generator.add_mapping(Mapping {
+        generator.push_mapping(Mapping {
             generated,
+            range: range_segments.contains(&(line, utils::segment(mapping))),
             original,
             source,
             name,
         })
     }
-    generator
-}
-
-fn remove_dup(vec: Vec<String>) -> Vec<Rc<String>> {
-    let mut new: Vec<Rc<String>> = Vec::new();
-    let mut set: HashSet<Rc<String>> = HashSet::new();
-
-    for i in vec.into_iter() {
-        let s = Rc::new(i);
-        if !set.contains(&s) {
-            new.push(s.clone());
-            set.insert(s);
-        }
-    }
-    new
+    Ok(generator)
 }
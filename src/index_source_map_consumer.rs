@@ -0,0 +1,225 @@
+use error::Error;
+use linked_hash_map::LinkedHashMap;
+use mapping::Mapping;
+use source_map::IndexSrcMap;
+use source_map_consumer::{Bias, MappingOrder, SourceMapConsumer};
+use source_map_generator::SourceMapGenerator;
+use utils;
+use Ptr;
+use StringPtr;
+
+#[derive(Debug)]
+pub struct IndexedSourceMapConsumer {
+    file: Option<Ptr<String>>,
+    // (line, column) of the first generated position of each section
+    sections: Vec<((usize, usize), SourceMapConsumer)>,
+    sources: Vec<Ptr<String>>,
+    names: Vec<Ptr<String>>,
+}
+
+impl IndexedSourceMapConsumer {
+    pub fn new(source_map: IndexSrcMap) -> Result<IndexedSourceMapConsumer, Error> {
+        let file = source_map.file.map(Ptr::new);
+        let mut sources: LinkedHashMap<Ptr<String>, ()> = LinkedHashMap::new();
+        let mut names: LinkedHashMap<Ptr<String>, ()> = LinkedHashMap::new();
+
+        let sections: Vec<((usize, usize), SourceMapConsumer)> = source_map
+            .sections
+            .into_iter()
+            .map(|section| {
+                let consumer = SourceMapConsumer::new(section.map)?;
+                for source in consumer.sources() {
+                    sources.entry(source.clone()).or_insert(());
+                }
+                for name in consumer.names() {
+                    names.entry(name.clone()).or_insert(());
+                }
+                // lines are stored 0-based in SourceMap spec version 3
+                Ok(((section.offset.line + 1, section.offset.column), consumer))
+            })
+            .collect::<Result<_, Error>>()?;
+        // lookups binary search the sections, which must not overlap
+        if let Some(pair) = sections.windows(2).find(|pair| pair[1].0 <= pair[0].0) {
+            let (line, column) = pair[1].0;
+            return Err(Error::InvalidSectionOffset(line, column));
+        }
+
+        Ok(IndexedSourceMapConsumer {
+            file,
+            sections,
+            sources: sources.keys().cloned().collect(),
+            names: names.keys().cloned().collect(),
+        })
+    }
+
+    pub fn from_json(source_map: &str) -> Result<IndexedSourceMapConsumer, Error> {
+        IndexedSourceMapConsumer::new(IndexSrcMap::from_json(source_map)?)
+    }
+
+    pub fn file(&self) -> Option<&Ptr<String>> {
+        self.file.as_ref()
+    }
+
+    pub fn sources(&self) -> &[Ptr<String>] {
+        &self.sources
+    }
+
+    pub fn names(&self) -> &[Ptr<String>] {
+        &self.names
+    }
+
+    pub fn has_contents_of_all_sources(&self) -> bool {
+        self.sections
+            .iter()
+            .all(|(_, consumer)| consumer.has_contents_of_all_sources())
+    }
+
+    pub fn source_content_for(&self, source: &str) -> Option<Ptr<String>> {
+        self.sections
+            .iter()
+            .filter_map(|(_, consumer)| consumer.source_content_for(source))
+            .next()
+    }
+
+    pub fn is_ignored(&self, source: &str) -> bool {
+        self.sections
+            .iter()
+            .any(|(_, consumer)| consumer.is_ignored(source))
+    }
+
+    // originate from `IndexedSourceMapConsumer.originalPositionFor`
+    pub fn original_position_for(&self, line: usize, column: usize, bias: Bias) -> Mapping {
+        let idx = utils::binary_search(
+            &self.sections,
+            &(line, column),
+            |section| section.0,
+            Bias::GreatestLowerBound,
+        );
+        if let Some(idx) = idx {
+            let (offset, ref consumer) = self.sections[idx];
+            let column = if line == offset.0 { column - offset.1 } else { column };
+            let mapping = consumer.original_position_for(line - offset.0 + 1, column, bias);
+            if mapping.generated.0 > 0 {
+                return to_global(offset, mapping);
+            }
+        }
+        Mapping {
+            generated: (0, 0),
+            source: None,
+            name: None,
+            original: None,
+            range: false,
+        }
+    }
+
+    // originate from `IndexedSourceMapConsumer.generatedPositionFor`
+    pub fn generated_position_for(
+        &self,
+        source: &str,
+        line: usize,
+        column: usize,
+        bias: Bias,
+    ) -> Mapping {
+        for &(offset, ref consumer) in &self.sections {
+            let mapping = consumer.generated_position_for(source, line, column, bias);
+            if mapping.generated.0 > 0 {
+                return to_global(offset, mapping);
+            }
+        }
+        Mapping {
+            generated: (0, 0),
+            source: None,
+            name: None,
+            original: None,
+            range: false,
+        }
+    }
+
+    pub fn all_generated_positions_for(
+        &self,
+        source: &str,
+        line: usize,
+        column: Option<usize>,
+    ) -> Vec<Mapping> {
+        self.sections
+            .iter()
+            .flat_map(|&(offset, ref consumer)| {
+                consumer
+                    .all_generated_positions_for(source, line, column)
+                    .into_iter()
+                    .map(move |mapping| to_global(offset, mapping))
+            })
+            .collect()
+    }
+
+    pub fn each_mapping<F>(&self, order: MappingOrder, mut f: F)
+    where
+        F: FnMut(&Mapping),
+    {
+        match order {
+            MappingOrder::Generated => {
+                for &(offset, ref consumer) in &self.sections {
+                    consumer.each_mapping(order, |mapping| f(&to_global(offset, mapping.clone())));
+                }
+            }
+            MappingOrder::Original => {
+                let mut original_mappings: Vec<Mapping> = Vec::new();
+                for &(offset, ref consumer) in &self.sections {
+                    consumer.each_mapping(order, |mapping| {
+                        original_mappings.push(to_global(offset, mapping.clone()))
+                    });
+                }
+                original_mappings.sort_by(|a, b| a.cmp_by_original(b));
+                for mapping in &original_mappings {
+                    f(mapping);
+                }
+            }
+        }
+    }
+
+    // flattens all sections into a single regular source map
+    pub fn to_source_map_generator(&self) -> SourceMapGenerator {
+        let file = self.file.clone().map(StringPtr::Ptr);
+        let mut generator = SourceMapGenerator::new(file, None, true);
+
+        for (i, &(offset, ref consumer)) in self.sections.iter().enumerate() {
+            let mut starts_with_mapping = i == 0;
+            consumer.each_mapping(MappingOrder::Generated, |mapping| {
+                let mapping = to_global(offset, mapping.clone());
+                starts_with_mapping = starts_with_mapping || mapping.generated == offset;
+                generator.push_mapping(mapping);
+            });
+            // keep the previous section's last mapping from spanning into this one
+            if !starts_with_mapping {
+                generator.push_mapping(Mapping {
+                    generated: offset,
+                    source: None,
+                    name: None,
+                    original: None,
+                    range: false,
+                });
+            }
+
+            for source in consumer.sources() {
+                if let Some(content) = consumer.source_content_for(source) {
+                    generator.set_source_content(
+                        StringPtr::Ptr(source.clone()),
+                        Some(StringPtr::Ptr(content)),
+                    );
+                }
+                if consumer.is_ignored(source) {
+                    generator.set_source_ignored(StringPtr::Ptr(source.clone()), true);
+                }
+            }
+        }
+        generator
+    }
+}
+
+fn to_global(offset: (usize, usize), mut mapping: Mapping) -> Mapping {
+    if mapping.generated.0 == 1 {
+        mapping.generated.1 += offset.1;
+    }
+    mapping.generated.0 += offset.0 - 1;
+    mapping
+}
@@ -0,0 +1,95 @@
+use error::Error;
+use index_source_map_consumer::IndexedSourceMapConsumer;
+use source_map::{IndexSrcMap, Offset, Section, SrcMap};
+use source_map_generator::SourceMapGenerator;
+use Ptr;
+use utils;
+use StringPtr;
+
+#[derive(Debug)]
+pub struct IndexedSourceMapGenerator {
+    file: Option<Ptr<String>>,
+    // each section along with the (line, column) of its last generated mapping
+    sections: Vec<(Section, (usize, usize))>,
+}
+
+impl IndexedSourceMapGenerator {
+    pub fn new(file: Option<StringPtr>) -> IndexedSourceMapGenerator {
+        IndexedSourceMapGenerator {
+            file: file.map(|sp| sp.to_ptr()),
+            sections: Vec::new(),
+        }
+    }
+
+    // `generated` is the (line, column) where the section starts in the concatenated output,
+    // sections must not overlap
+    pub fn add_section(&mut self, generated: (usize, usize), map: SrcMap) -> Result<(), Error> {
+        // lines are stored 0-based in SourceMap spec version 3
+        let last = utils::last_generated_position(&map.mappings)?.map(|(line, column)| (line + 1, column));
+        self.insert_section(generated, map, last)
+    }
+
+    pub fn add_generator(
+        &mut self,
+        generated: (usize, usize),
+        generator: &mut SourceMapGenerator,
+    ) -> Result<(), Error> {
+        generator.mappings.sort();
+        let last = generator.mappings.list.last().map(|mapping| mapping.generated);
+        let map = generator.to_source_map()?;
+        self.insert_section(generated, map, last)
+    }
+
+    // `last` is the position of the last mapping of `map`
+    fn insert_section(
+        &mut self,
+        generated: (usize, usize),
+        map: SrcMap,
+        last: Option<(usize, usize)>,
+    ) -> Result<(), Error> {
+        if generated.0 == 0 {
+            return Err(Error::InvalidSectionOffset(generated.0, generated.1));
+        }
+        let offset = Offset {
+            // lines are stored 0-based in SourceMap spec version 3
+            line: generated.0 - 1,
+            column: generated.1,
+        };
+        let end = match last {
+            Some((1, column)) => (generated.0, generated.1 + column),
+            Some((line, column)) => (generated.0 + line - 1, column),
+            None => generated,
+        };
+        let idx = self
+            .sections
+            .iter()
+            .position(|(section, _)| section.offset > offset)
+            .unwrap_or(self.sections.len());
+        let overlaps_previous = idx > 0 && self.sections[idx - 1].1 >= generated;
+        let overlaps_next = self.sections.get(idx).is_some_and(|(section, _)| {
+            // lines are stored 0-based in SourceMap spec version 3
+            end >= (section.offset.line + 1, section.offset.column)
+        });
+        if overlaps_previous || overlaps_next {
+            return Err(Error::InvalidSectionOffset(generated.0, generated.1));
+        }
+        self.sections.insert(idx, (Section { offset, map }, end));
+        Ok(())
+    }
+
+    pub fn to_index_source_map(&self) -> IndexSrcMap {
+        IndexSrcMap {
+            version: 3,
+            file: self.file.clone().map(|sp| (*sp).clone()),
+            sections: self.sections.iter().map(|(section, _)| section.clone()).collect(),
+        }
+    }
+
+    pub fn to_source_map_generator(&self) -> Result<SourceMapGenerator, Error> {
+        Ok(IndexedSourceMapConsumer::new(self.to_index_source_map())?.to_source_map_generator())
+    }
+
+    pub fn to_source_map(&self) -> Result<SrcMap, Error> {
+        self.to_source_map_generator()?.to_source_map()
+    }
+}
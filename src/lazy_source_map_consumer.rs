@@ -0,0 +1,182 @@
+use column_unit::{convert_column, ColumnUnit};
+use error::Error;
+use line_terminators::LineTerminators;
+use mapping::Mapping;
+use source_map::SrcMapRef;
+use source_map_consumer::{
+    content_line, convert_mapping, decoded_mapping, original_position_in, resolve_sources, source_index, Bias,
+};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use utils::{self, Segment};
+use Ptr;
+
+// a consumer that only decodes the mappings of the generated lines a lookup touches,
+// for maps that are queried a few times and then thrown away; lookups by original
+// position would need every line decoded, so they are left to `SourceMapConsumer`;
+// the mappings and source contents stay borrowed from the map it is built from
+#[derive(Debug)]
+pub struct LazySourceMapConsumer<'a> {
+    file: Option<Ptr<String>>,
+    source_root: Option<Ptr<String>>,
+    sources: Vec<Ptr<String>>,
+    source_indices: HashMap<Ptr<String>, usize>,
+    names: Vec<Ptr<String>>,
+    sources_content: Vec<Option<Cow<'a, str>>>,
+    mappings: Cow<'a, str>,
+    // offset of each generated line in `mappings`
+    line_offsets: Vec<usize>,
+    range_mappings: Vec<Vec<usize>>,
+    // source, original line, original column and name when each line starts, since
+    // they are encoded relative to the previous segment across lines
+    line_states: Vec<[i64; 4]>,
+    lines: HashMap<usize, Vec<Mapping>>,
+    column_unit: ColumnUnit,
+    line_terminators: LineTerminators,
+}
+
+impl<'a> LazySourceMapConsumer<'a> {
+    // takes a `SrcMap`, a `&SrcMap` or a `SrcMapRef`
+    pub fn new<M: Into<SrcMapRef<'a>>>(source_map: M) -> Result<LazySourceMapConsumer<'a>, Error> {
+        let source_map = source_map.into();
+        let source_root = source_map.source_root.map(|root| Ptr::new(root.into_owned()));
+        let (sources, source_indices) = resolve_sources(&source_root, &source_map.sources);
+        let line_offsets = Some(0)
+            .into_iter()
+            .chain(source_map.mappings.match_indices(';').map(|(idx, _)| idx + 1))
+            .collect();
+        let range_mappings = match source_map.range_mappings {
+            Some(ref range_mappings) => utils::parse_range_mappings(range_mappings)?,
+            None => Vec::new(),
+        };
+
+        Ok(LazySourceMapConsumer {
+            file: source_map.file.map(|file| Ptr::new(file.into_owned())),
+            source_root,
+            sources,
+            source_indices,
+            names: source_map.names.into_iter().map(|name| Ptr::new(name.into_owned())).collect(),
+            sources_content: source_map.sources_content,
+            mappings: source_map.mappings,
+            line_offsets,
+            range_mappings,
+            line_states: vec![[0; 4]],
+            lines: HashMap::new(),
+            column_unit: ColumnUnit::default(),
+            line_terminators: LineTerminators::default(),
+        })
+    }
+
+    pub fn from_json(source_map: &'a str) -> Result<LazySourceMapConsumer<'a>, Error> {
+        LazySourceMapConsumer::new(SrcMapRef::from_json(source_map)?)
+    }
+
+    pub fn file(&self) -> Option<&Ptr<String>> {
+        self.file.as_ref()
+    }
+
+    pub fn source_root(&self) -> Option<&Ptr<String>> {
+        self.source_root.as_ref()
+    }
+
+    // the unit the columns of the map are counted in
+    pub fn column_unit(&self) -> ColumnUnit {
+        self.column_unit
+    }
+
+    pub fn set_column_unit(&mut self, column_unit: ColumnUnit) {
+        self.column_unit = column_unit;
+    }
+
+    // the line terminators of the source contents
+    pub fn line_terminators(&self) -> LineTerminators {
+        self.line_terminators
+    }
+
+    pub fn set_line_terminators(&mut self, line_terminators: LineTerminators) {
+        self.line_terminators = line_terminators;
+    }
+
+    pub fn sources(&self) -> &[Ptr<String>] {
+        &self.sources
+    }
+
+    pub fn names(&self) -> &[Ptr<String>] {
+        &self.names
+    }
+
+    pub fn source_content_for(&self, source: &str) -> Option<&str> {
+        source_index(&self.source_indices, &self.source_root, source)
+            .and_then(|idx| self.sources_content.get(idx))
+            .and_then(|content| content.as_ref())
+            .map(|content| content.as_ref())
+    }
+
+    // mappings of a generated line in the order of `SourceMapConsumer`, decoding the line on first use
+    pub fn mappings_for_line(&mut self, line: usize) -> Result<&[Mapping], Error> {
+        if line == 0 || line > self.line_offsets.len() {
+            return Ok(&[]);
+        }
+        // lines are stored 0-based in SourceMap spec version 3
+        let idx = line - 1;
+        if !self.lines.contains_key(&idx) {
+            while self.line_states.len() <= idx {
+                let mut state = self.line_states[self.line_states.len() - 1];
+                self.decode_line(self.line_states.len() - 1, &mut state, None)?;
+                self.line_states.push(state);
+            }
+            let mut state = self.line_states[idx];
+            let mut segments = Vec::new();
+            self.decode_line(idx, &mut state, Some(&mut segments))?;
+            // by column, then mapped segments first, by source, original position and name
+            segments.sort_by_key(|&((column, original), _)| (column, original.is_none(), original));
+            let mappings = segments
+                .into_iter()
+                .map(|((column, original), range)| {
+                    decoded_mapping((idx, column), original, range, &self.sources, &self.names)
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            if self.line_states.len() == idx + 1 {
+                self.line_states.push(state);
+            }
+            self.lines.insert(idx, mappings);
+        }
+        Ok(&self.lines[&idx])
+    }
+
+    // same results as `SourceMapConsumer.original_position_for`
+    pub fn original_position_for(&mut self, line: usize, column: usize, bias: Bias) -> Result<Mapping, Error> {
+        Ok(original_position_in(self.mappings_for_line(line)?, line, column, bias))
+    }
+
+    // same results as `SourceMapConsumer.original_position_for_in`
+    pub fn original_position_for_in(
+        &mut self,
+        generated_line: &str,
+        line: usize,
+        column: usize,
+        column_unit: ColumnUnit,
+        bias: Bias,
+    ) -> Result<Mapping, Error> {
+        let column = convert_column(generated_line, column, column_unit, self.column_unit);
+        let mapping = self.original_position_for(line, column, bias)?;
+        Ok(convert_mapping(mapping, generated_line, self.column_unit, column_unit, |source, line| {
+            let idx = source_index(&self.source_indices, &self.source_root, source)?;
+            content_line(self.sources_content.get(idx)?.as_ref()?, self.line_terminators, line)
+        }))
+    }
+
+    // advances `state` over the segments of line `idx`, collecting them with their range
+    // flag into `out` when given
+    fn decode_line(&self, idx: usize, state: &mut [i64; 4], mut out: Option<&mut Vec<(Segment, bool)>>) -> Result<(), Error> {
+        let start = self.line_offsets[idx];
+        let end = self.line_offsets.get(idx + 1).map_or(self.mappings.len(), |next| next - 1);
+        utils::decode_line(self.mappings.as_bytes(), start, end, state, |segment| {
+            if let Some(ref mut out) = out {
+                // segments are collected in their encoded order
+                let range = utils::is_range_mapping(&self.range_mappings, idx, out.len());
+                out.push((segment, range));
+            }
+        })
+    }
+}
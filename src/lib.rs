@@ -1,5 +1,7 @@
+extern crate base64;
 extern crate linked_hash_map;
 extern crate regex;
+extern crate serde;
 extern crate vlq;
 #[macro_use]
 extern crate lazy_static;
@@ -8,46 +10,154 @@ extern crate serde_derive;
 extern crate serde_json;
 extern crate source_map_mappings;
 
+mod column_unit;
+mod debug_id;
+mod error;
+mod index_source_map_consumer;
+mod index_source_map_generator;
+mod lazy_source_map_consumer;
+mod magic_string;
+mod line_terminators;
 mod mapping;
 mod mapping_list;
+mod remap;
+mod scopes;
 mod source_map;
 mod source_map_consumer;
 mod source_map_generator;
 mod source_node;
+mod source_mapping_url;
 mod utils;
 mod from_source_map;
 
+pub use column_unit::{convert_column, ColumnUnit};
+pub use debug_id::{debug_id_comment, generate_debug_id, parse_debug_id};
+pub use error::Error;
+pub use from_source_map::from_source_map;
+pub use index_source_map_consumer::*;
+pub use index_source_map_generator::*;
+pub use lazy_source_map_consumer::*;
+pub use line_terminators::LineTerminators;
+pub use magic_string::MagicString;
 pub use mapping::Mapping;
+pub use remap::remap;
+pub use scopes::{CallSite, GeneratedRange, OriginalScope, ScopeDefinition};
+pub use source_map::*;
+pub use source_map_consumer::*;
 pub use source_map_generator::*;
 pub use source_node::*;
-use std::rc::Rc;
+pub use source_mapping_url::*;
+
+// with the `sync` feature every shared string is an `Arc`, which makes nodes, mappings
+// and generators `Send + Sync` so they can be built on several threads and combined
+#[cfg(not(feature = "sync"))]
+pub type Ptr<T> = std::rc::Rc<T>;
+#[cfg(feature = "sync")]
+pub type Ptr<T> = std::sync::Arc<T>;
 
 #[derive(Clone, Debug)]
 pub enum Node {
     NSourceNode(SourceNode),
     NString(String),
-    NRcString(Rc<String>),
+    NRcString(Ptr<String>),
     NNodeVec(Vec<Node>),
 }
 
+impl<'a> From<&'a str> for Node {
+    fn from(s: &'a str) -> Node {
+        Node::NString(String::from(s))
+    }
+}
+
+impl From<String> for Node {
+    fn from(s: String) -> Node {
+        Node::NString(s)
+    }
+}
+
+impl From<Ptr<String>> for Node {
+    fn from(s: Ptr<String>) -> Node {
+        Node::NRcString(s)
+    }
+}
+
+impl From<SourceNode> for Node {
+    fn from(sn: SourceNode) -> Node {
+        Node::NSourceNode(sn)
+    }
+}
+
+impl<T: Into<Node>> From<Vec<T>> for Node {
+    fn from(nodes: Vec<T>) -> Node {
+        Node::NNodeVec(nodes.into_iter().map(Into::into).collect())
+    }
+}
+
+// an optional argument of `SourceNode::new`, given as the value itself, `Some` of it or `None`
+pub trait IntoOption<T> {
+    fn into_option(self) -> Option<T>;
+}
+
+impl<T: Into<Node>> IntoOption<Node> for T {
+    fn into_option(self) -> Option<Node> {
+        Some(self.into())
+    }
+}
+
+impl IntoOption<Node> for Option<Node> {
+    fn into_option(self) -> Option<Node> {
+        self
+    }
+}
+
+impl<T: Into<StringPtr>> IntoOption<StringPtr> for T {
+    fn into_option(self) -> Option<StringPtr> {
+        Some(self.into())
+    }
+}
+
+impl IntoOption<StringPtr> for Option<StringPtr> {
+    fn into_option(self) -> Option<StringPtr> {
+        self
+    }
+}
+
 #[derive(Clone)]
 pub enum StringPtr {
     Str(String),
-    Ptr(Rc<String>),
+    Ptr(Ptr<String>),
 }
 
 impl StringPtr {
-    pub fn to_ptr(self) -> Rc<String> {
+    pub fn to_ptr(self) -> Ptr<String> {
         match self {
-            StringPtr::Str(s) => Rc::new(s),
+            StringPtr::Str(s) => Ptr::new(s),
             StringPtr::Ptr(p) => p,
         }
     }
 
     pub fn get(&self) -> &str {
         match self {
-            StringPtr::Str(s) => &s,
+            StringPtr::Str(s) => s,
             StringPtr::Ptr(p) => p,
         }
     }
 }
+
+impl<'a> From<&'a str> for StringPtr {
+    fn from(s: &'a str) -> StringPtr {
+        StringPtr::Str(String::from(s))
+    }
+}
+
+impl From<String> for StringPtr {
+    fn from(s: String) -> StringPtr {
+        StringPtr::Str(s)
+    }
+}
+
+impl From<Ptr<String>> for StringPtr {
+    fn from(p: Ptr<String>) -> StringPtr {
+        StringPtr::Ptr(p)
+    }
+}
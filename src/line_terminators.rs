@@ -0,0 +1,49 @@
+// the characters that end a line of generated or original code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LineTerminators {
+    // `\n` only
+    Lf,
+    // `\n`, `\r\n`, `\r`, U+2028 and U+2029, as in ECMAScript
+    #[default]
+    EcmaScript,
+    // `\n`, `\r\n`, `\r` and `\f`, as in CSS
+    Css,
+}
+
+impl LineTerminators {
+    // `\r\n` is a single terminator, its `\r` is the one matched here
+    pub fn is_terminator(self, c: char) -> bool {
+        match self {
+            LineTerminators::Lf => c == '\n',
+            LineTerminators::EcmaScript => {
+                c == '\n' || c == '\r' || c == '\u{2028}' || c == '\u{2029}'
+            }
+            LineTerminators::Css => c == '\n' || c == '\r' || c == '\u{c}',
+        }
+    }
+
+    // the lines of `s` with their terminators, the last one (possibly empty) has none
+    pub fn split_lines(self, s: &str) -> Vec<&str> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut chars = s.char_indices().peekable();
+        while let Some((offset, c)) = chars.next() {
+            if !self.is_terminator(c) {
+                continue;
+            }
+            let mut end = offset + c.len_utf8();
+            if c == '\r' && chars.peek().map(|&(_, c)| c) == Some('\n') {
+                chars.next();
+                end += 1;
+            }
+            lines.push(&s[start..end]);
+            start = end;
+        }
+        lines.push(&s[start..]);
+        lines
+    }
+
+    pub fn trim_terminator(self, line: &str) -> &str {
+        line.trim_end_matches(|c| self.is_terminator(c))
+    }
+}
@@ -0,0 +1,339 @@
+use column_unit::ColumnUnit;
+use error::Error;
+use line_terminators::LineTerminators;
+use source_map::StringWithSrcMap;
+use source_map_generator::SourceMapGenerator;
+use source_node::SourceNode;
+use std::fmt;
+use std::mem;
+use Ptr;
+use StringPtr;
+
+// originate from the `magic-string` package: edits are recorded against the original
+// text, so regions left untouched keep mapping back to where they came from
+#[derive(Clone, Debug)]
+pub struct MagicString {
+    original: Ptr<String>,
+    // byte offset of the start of each original line
+    line_starts: Vec<usize>,
+    intro: String,
+    outro: String,
+    // in output order, covering the original text
+    chunks: Vec<Chunk>,
+    index_unit: ColumnUnit,
+}
+
+#[derive(Clone, Debug)]
+struct Chunk {
+    // byte range in the original text
+    start: usize,
+    end: usize,
+    intro: String,
+    outro: String,
+    // `None` while the original text is untouched
+    content: Option<String>,
+}
+
+impl MagicString {
+    pub fn new(original: impl Into<StringPtr>) -> MagicString {
+        let original = original.into().to_ptr();
+        let mut line_starts = Vec::new();
+        let mut offset = 0;
+        for line in LineTerminators::default().split_lines(&original) {
+            line_starts.push(offset);
+            offset += line.len();
+        }
+        let chunk = Chunk {
+            start: 0,
+            end: original.len(),
+            intro: String::new(),
+            outro: String::new(),
+            content: None,
+        };
+        MagicString {
+            original,
+            line_starts,
+            intro: String::new(),
+            outro: String::new(),
+            chunks: vec![chunk],
+            index_unit: ColumnUnit::Byte,
+        }
+    }
+
+    pub fn original(&self) -> &str {
+        &self.original
+    }
+
+    // the unit indices passed to the editing methods are counted in, bytes by default
+    pub fn index_unit(&self) -> ColumnUnit {
+        self.index_unit
+    }
+
+    pub fn set_index_unit(&mut self, index_unit: ColumnUnit) {
+        self.index_unit = index_unit;
+    }
+
+    pub fn prepend(&mut self, content: &str) {
+        self.intro.insert_str(0, content);
+    }
+
+    pub fn append(&mut self, content: &str) {
+        self.outro += content;
+    }
+
+    // the `_left` variants stick to the text ending at `index`, the `_right` ones to the
+    // text starting there, which matters once either side is moved or removed
+    pub fn prepend_left(&mut self, index: usize, content: &str) -> Result<(), Error> {
+        let index = self.split_at(index)?;
+        self.outro_ending_at(index).insert_str(0, content);
+        Ok(())
+    }
+
+    pub fn append_left(&mut self, index: usize, content: &str) -> Result<(), Error> {
+        let index = self.split_at(index)?;
+        *self.outro_ending_at(index) += content;
+        Ok(())
+    }
+
+    pub fn prepend_right(&mut self, index: usize, content: &str) -> Result<(), Error> {
+        let index = self.split_at(index)?;
+        self.intro_starting_at(index).insert_str(0, content);
+        Ok(())
+    }
+
+    pub fn append_right(&mut self, index: usize, content: &str) -> Result<(), Error> {
+        let index = self.split_at(index)?;
+        *self.intro_starting_at(index) += content;
+        Ok(())
+    }
+
+    // the replacement maps to the start of the range; like in `magic-string`, text
+    // inserted at the chunks of the range goes with them
+    pub fn overwrite(&mut self, start: usize, end: usize, content: &str) -> Result<(), Error> {
+        let (from, to) = self.range(start, end)?;
+        if from == to {
+            return Err(Error::InvalidRange(start, end));
+        }
+        self.check_split(start, from)?;
+        self.check_split(end, to)?;
+        self.split_at(start)?;
+        self.split_at(end)?;
+        for chunk in &mut self.chunks {
+            if chunk.start >= from && chunk.end <= to {
+                chunk.clear();
+                if chunk.start == from {
+                    chunk.content = Some(String::from(content));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, start: usize, end: usize) -> Result<(), Error> {
+        let (from, to) = self.range(start, end)?;
+        self.check_split(start, from)?;
+        self.check_split(end, to)?;
+        self.split_at(start)?;
+        self.split_at(end)?;
+        for chunk in &mut self.chunks {
+            if chunk.start >= from && chunk.end <= to && chunk.start < chunk.end {
+                chunk.clear();
+            }
+        }
+        Ok(())
+    }
+
+    // moves `start..end` in front of the text starting at `index`, or to the end when
+    // `index` is the length of the original text
+    pub fn move_to(&mut self, start: usize, end: usize, index: usize) -> Result<(), Error> {
+        let (from, to) = self.range(start, end)?;
+        let target = self.byte_index(index).ok_or(Error::InvalidRange(index, index))?;
+        if from < target && target < to {
+            return Err(Error::InvalidRange(start, end));
+        }
+        if from == to || target == from {
+            return Ok(());
+        }
+        self.check_split(start, from)?;
+        self.check_split(end, to)?;
+        self.check_split(index, target)?;
+        self.split_at(start)?;
+        self.split_at(end)?;
+        self.split_at(index)?;
+        let first = self.chunks.iter().position(|chunk| chunk.start == from);
+        let last = self.chunks.iter().position(|chunk| chunk.end == to);
+        let (first, last) = match (first, last) {
+            (Some(first), Some(last)) if first <= last => (first, last),
+            _ => return Err(Error::InvalidRange(start, end)),
+        };
+        // an earlier move may have broken the range apart or put other text in between
+        let moved = &self.chunks[first..=last];
+        if moved.windows(2).any(|pair| pair[0].end != pair[1].start) {
+            return Err(Error::InvalidRange(start, end));
+        }
+        let moved: Vec<Chunk> = self.chunks.drain(first..=last).collect();
+        let idx = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.start == target && chunk.start < chunk.end)
+            .unwrap_or(self.chunks.len());
+        self.chunks.splice(idx..idx, moved);
+        Ok(())
+    }
+
+    // untouched text maps line by line to the original, inserted text is unmapped
+    pub fn to_source_node(&self, source: impl Into<StringPtr>) -> SourceNode {
+        let source = source.into().to_ptr();
+        let mut node = SourceNode::default();
+        let add_text = |node: &mut SourceNode, text: &str| {
+            if !text.is_empty() {
+                node.add(text);
+            }
+        };
+        add_text(&mut node, &self.intro);
+        for chunk in &self.chunks {
+            add_text(&mut node, &chunk.intro);
+            match chunk.content {
+                Some(ref content) if !content.is_empty() => {
+                    let (line, column) = self.position(chunk.start);
+                    node.add(SourceNode::at(line, column, source.clone()).child(content.as_str()));
+                }
+                Some(_) => {}
+                None => {
+                    let mut offset = chunk.start;
+                    let text = &self.original[chunk.start..chunk.end];
+                    for line in LineTerminators::default().split_lines(text) {
+                        if !line.is_empty() {
+                            let (line_no, column) = self.position(offset);
+                            node.add(SourceNode::at(line_no, column, source.clone()).child(line));
+                        }
+                        offset += line.len();
+                    }
+                }
+            }
+            add_text(&mut node, &chunk.outro);
+        }
+        add_text(&mut node, &self.outro);
+        node.set_source_content(source, self.original.clone());
+        node
+    }
+
+    pub fn to_source_map_generator(
+        &self,
+        source: impl Into<StringPtr>,
+        file: Option<StringPtr>,
+    ) -> SourceMapGenerator {
+        self.to_source_node(source).to_source_map_generator(file, None)
+    }
+
+    pub fn to_string_with_source_map(
+        &self,
+        source: impl Into<StringPtr>,
+        file: Option<StringPtr>,
+    ) -> Result<StringWithSrcMap, Error> {
+        self.to_source_node(source).to_string_with_source_map(file, None)
+    }
+
+    fn byte_index(&self, index: usize) -> Option<usize> {
+        if self.index_unit == ColumnUnit::Byte {
+            return if self.original.is_char_boundary(index) { Some(index) } else { None };
+        }
+        let byte = self.index_unit.byte_offset(&self.original, index);
+        if self.index_unit.column_at(&self.original, byte) == index {
+            Some(byte)
+        } else {
+            None
+        }
+    }
+
+    fn range(&self, start: usize, end: usize) -> Result<(usize, usize), Error> {
+        match (self.byte_index(start), self.byte_index(end)) {
+            (Some(from), Some(to)) if from <= to => Ok((from, to)),
+            _ => Err(Error::InvalidRange(start, end)),
+        }
+    }
+
+    // fails when `byte`, the offset of `index`, falls inside edited text and so cannot
+    // become a chunk boundary
+    fn check_split(&self, index: usize, byte: usize) -> Result<(), Error> {
+        let edited = self
+            .chunks
+            .iter()
+            .any(|chunk| chunk.start < byte && byte < chunk.end && chunk.content.is_some());
+        if edited {
+            Err(Error::EditedRange(index))
+        } else {
+            Ok(())
+        }
+    }
+
+    // makes `index` a chunk boundary and returns it as a byte offset
+    fn split_at(&mut self, index: usize) -> Result<usize, Error> {
+        let byte = self.byte_index(index).ok_or(Error::InvalidRange(index, index))?;
+        self.check_split(index, byte)?;
+        let idx = match self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.start < byte && byte < chunk.end)
+        {
+            Some(idx) => idx,
+            None => return Ok(byte),
+        };
+        let chunk = &mut self.chunks[idx];
+        let tail = Chunk {
+            start: byte,
+            end: chunk.end,
+            intro: String::new(),
+            outro: mem::take(&mut chunk.outro),
+            content: None,
+        };
+        chunk.end = byte;
+        self.chunks.insert(idx + 1, tail);
+        Ok(byte)
+    }
+
+    fn outro_ending_at(&mut self, byte: usize) -> &mut String {
+        match self.chunks.iter_mut().find(|chunk| chunk.end == byte && chunk.start < byte) {
+            Some(chunk) => &mut chunk.outro,
+            None => &mut self.intro,
+        }
+    }
+
+    fn intro_starting_at(&mut self, byte: usize) -> &mut String {
+        match self.chunks.iter_mut().find(|chunk| chunk.start == byte && byte < chunk.end) {
+            Some(chunk) => &mut chunk.intro,
+            None => &mut self.outro,
+        }
+    }
+
+    // (line, column) of a byte offset of the original text
+    fn position(&self, byte: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= byte) - 1;
+        let line_start = self.line_starts[line];
+        let column = ColumnUnit::default().width(&self.original[line_start..byte]);
+        (line + 1, column)
+    }
+}
+
+impl Chunk {
+    fn clear(&mut self) {
+        self.intro.clear();
+        self.outro.clear();
+        self.content = Some(String::new());
+    }
+}
+
+impl fmt::Display for MagicString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.intro)?;
+        for chunk in &self.chunks {
+            f.write_str(&chunk.intro)?;
+            match chunk.content {
+                Some(ref content) => f.write_str(content)?,
+                None => f.write_str(&self.original[chunk.start..chunk.end])?,
+            }
+            f.write_str(&chunk.outro)?;
+        }
+        f.write_str(&self.outro)
+    }
+}
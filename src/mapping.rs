@@ -1,24 +1,58 @@
 use std::cmp::Ordering;
-use std::rc::Rc;
+use Ptr;
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct Mapping {
     // (line, column)
     pub generated: (usize, usize),
-    pub source: Option<Rc<String>>,
-    pub name: Option<Rc<String>>,
+    pub source: Option<Ptr<String>>,
+    pub name: Option<Ptr<String>>,
     pub original: Option<(usize, usize)>,
+    // a range mapping also maps every following column of its generated line, up to the
+    // next mapping, to the column at the same distance on its original line
+    pub range: bool,
 }
 
 impl Mapping {
     pub fn from_tuple(input: (String, usize, usize, usize, usize, Option<String>)) -> Mapping {
         Mapping {
             generated: (input.1, input.2),
-            source: Some(Rc::new(input.0)),
-            name: input.5.map(|s| Rc::new(s)),
+            source: Some(Ptr::new(input.0)),
+            name: input.5.map(Ptr::new),
             original: Some((input.3, input.4)),
+            range: false,
         }
     }
+
+    // the position `column` maps to when it falls inside this range mapping
+    pub(crate) fn at_column(&self, column: usize) -> Mapping {
+        let mut mapping = self.clone();
+        if let Some(ref mut original) = mapping.original {
+            original.1 += column - mapping.generated.1;
+        }
+        mapping.generated.1 = column;
+        mapping
+    }
+
+    // originate from `util.compareByOriginalPositions`
+    pub fn cmp_by_original(&self, other: &Mapping) -> Ordering {
+        let cmp = strcmp(&self.source, &other.source);
+        if cmp != Ordering::Equal {
+            return cmp;
+        }
+
+        let cmp = self.original.cmp(&other.original);
+        if cmp != Ordering::Equal {
+            return cmp;
+        }
+
+        let cmp = self.generated.cmp(&other.generated);
+        if cmp != Ordering::Equal {
+            return cmp;
+        }
+
+        strcmp(&self.name, &other.name)
+    }
 }
 
 impl Ord for Mapping {
@@ -38,7 +72,7 @@ impl Ord for Mapping {
             return cmp;
         }
 
-        return strcmp(&self.name, &other.name);
+        strcmp(&self.name, &other.name)
     }
 }
 
@@ -48,7 +82,7 @@ impl PartialOrd for Mapping {
     }
 }
 
-fn strcmp(s1: &Option<Rc<String>>, s2: &Option<Rc<String>>) -> Ordering {
+fn strcmp(s1: &Option<Ptr<String>>, s2: &Option<Ptr<String>>) -> Ordering {
     if s1.is_none() && s2.is_some() {
         Ordering::Greater
     } else if s2.is_none() && s1.is_some() {
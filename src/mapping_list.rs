@@ -32,6 +32,14 @@ impl MappingList {
         }
     }
 
+    pub fn unsorted_for_each_mut<F>(&mut self, f: F)
+    where
+        F: FnMut(&mut Mapping),
+    {
+        self.list.iter_mut().for_each(f);
+        self.sorted = false;
+    }
+
     pub fn sort(&mut self) {
         if !self.sorted {
             self.list.sort();
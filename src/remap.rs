@@ -0,0 +1,142 @@
+use error::Error;
+use mapping::Mapping;
+use source_map::SrcMap;
+use source_map_consumer::{Bias, MappingOrder, SourceMapConsumer};
+use source_map_generator::SourceMapGenerator;
+use std::collections::HashMap;
+use utils;
+use Ptr;
+use StringPtr;
+
+// flattens a chain of maps: `loader` is called with every source of `source_map` and
+// may return that source's own map, which is remapped in turn until the leaves; mappings
+// that cannot be traced down to a leaf are dropped; range mappings stay ranges only for
+// sources without a map of their own, the columns past their start may map anywhere else
+pub fn remap<F>(source_map: SrcMap, mut loader: F) -> Result<SourceMapGenerator, Error>
+where
+    F: FnMut(&str) -> Option<SrcMap>,
+{
+    let file = source_map.file.clone().map(StringPtr::Str);
+    let tree = RemapNode::new(SourceMapConsumer::new(source_map)?, None, &mut loader, &mut Vec::new())?;
+
+    let mut generator = SourceMapGenerator::new(file, None, true);
+    tree.consumer.each_mapping(MappingOrder::Generated, |mapping| {
+        if mapping.source.is_none() {
+            generator.push_mapping(mapping.clone());
+            return;
+        }
+        if let Some(leaf) = tree.trace(mapping) {
+            if let Some(content) = leaf.node.consumer.source_content_for(&leaf.node.consumer.sources()[leaf.idx]) {
+                generator.set_source_content(StringPtr::Ptr(leaf.source.clone()), Some(StringPtr::Ptr(content)));
+            }
+            if leaf.node.consumer.is_ignored(&leaf.node.consumer.sources()[leaf.idx]) {
+                generator.set_source_ignored(StringPtr::Ptr(leaf.source.clone()), true);
+            }
+            generator.push_mapping(Mapping {
+                generated: mapping.generated,
+                source: Some(leaf.source),
+                name: leaf.name,
+                original: leaf.original,
+                range: leaf.range,
+            });
+        }
+    });
+    Ok(generator)
+}
+
+struct RemapNode {
+    consumer: SourceMapConsumer,
+    // sources of `consumer` resolved against the source this map was loaded for
+    sources: Vec<Ptr<String>>,
+    source_indices: HashMap<Ptr<String>, usize>,
+    children: Vec<Option<RemapNode>>,
+}
+
+struct Leaf<'a> {
+    node: &'a RemapNode,
+    idx: usize,
+    source: Ptr<String>,
+    original: Option<(usize, usize)>,
+    name: Option<Ptr<String>>,
+    range: bool,
+}
+
+impl RemapNode {
+    fn new<F>(
+        consumer: SourceMapConsumer,
+        parent_source: Option<&str>,
+        loader: &mut F,
+        // sources currently being loaded, to stop on cycles
+        loading: &mut Vec<Ptr<String>>,
+    ) -> Result<RemapNode, Error>
+    where
+        F: FnMut(&str) -> Option<SrcMap>,
+    {
+        let dir = parent_source.and_then(|source| source.rfind('/').map(|idx| &source[..idx]));
+        let sources: Vec<Ptr<String>> = consumer
+            .sources()
+            .iter()
+            .map(|source| match dir {
+                Some(dir) if !utils::is_absolute(source) => Ptr::new(utils::join(dir, source)),
+                _ => source.clone(),
+            })
+            .collect();
+
+        let mut source_indices = HashMap::new();
+        let mut children = Vec::new();
+        for (idx, source) in consumer.sources().iter().enumerate() {
+            source_indices.entry(source.clone()).or_insert(idx);
+            let resolved = sources[idx].clone();
+            if loading.contains(&resolved) {
+                children.push(None);
+                continue;
+            }
+            let child = match loader(&resolved) {
+                Some(source_map) => {
+                    loading.push(resolved.clone());
+                    let child = SourceMapConsumer::new(source_map)
+                        .and_then(|child| RemapNode::new(child, Some(&resolved), loader, loading));
+                    loading.pop();
+                    Some(child?)
+                }
+                None => None,
+            };
+            children.push(child);
+        }
+
+        Ok(RemapNode {
+            consumer,
+            sources,
+            source_indices,
+            children,
+        })
+    }
+
+    // follows a mapping of this node's consumer down to the leaf source it comes from;
+    // names of deeper maps take precedence over the ones of the maps applied to them
+    fn trace(&self, mapping: &Mapping) -> Option<Leaf<'_>> {
+        let idx = *self.source_indices.get(mapping.source.as_ref()?)?;
+        let leaf = match self.children[idx] {
+            None => Leaf {
+                node: self,
+                idx,
+                source: self.sources[idx].clone(),
+                original: mapping.original,
+                name: None,
+                range: mapping.range,
+            },
+            Some(ref child) => {
+                let (line, column) = mapping.original?;
+                let inner = child.consumer.original_position_for(line, column, Bias::GreatestLowerBound);
+                Leaf {
+                    range: false,
+                    ..child.trace(&inner)?
+                }
+            }
+        };
+        Some(Leaf {
+            name: leaf.name.or_else(|| mapping.name.clone()),
+            ..leaf
+        })
+    }
+}
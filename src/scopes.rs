@@ -0,0 +1,426 @@
+use error::Error;
+use vlq;
+
+// positions are (line, column) with 1-based lines, like `Mapping`, and `end` is exclusive
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginalScope {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub name: Option<String>,
+    // e.g. "global", "function", "block"
+    pub kind: Option<String>,
+    pub is_stack_frame: bool,
+    pub variables: Vec<String>,
+    pub children: Vec<OriginalScope>,
+}
+
+// refers to the `index`-th scope of `source`, counting its scope trees in pre-order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopeDefinition {
+    pub source: String,
+    pub index: usize,
+}
+
+// original position of the call an inlined range was expanded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallSite {
+    pub source: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedRange {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub definition: Option<ScopeDefinition>,
+    pub call_site: Option<CallSite>,
+    pub is_stack_frame: bool,
+    pub is_hidden: bool,
+    // expression holding each variable of the definition scope, `None` when unavailable
+    pub bindings: Vec<Option<String>>,
+    pub children: Vec<GeneratedRange>,
+}
+
+const SCOPE_HAS_NAME: i64 = 0x1;
+const SCOPE_HAS_KIND: i64 = 0x2;
+const SCOPE_IS_STACK_FRAME: i64 = 0x4;
+
+const RANGE_HAS_DEFINITION: i64 = 0x1;
+const RANGE_HAS_CALL_SITE: i64 = 0x2;
+const RANGE_IS_STACK_FRAME: i64 = 0x4;
+const RANGE_IS_HIDDEN: i64 = 0x8;
+
+impl OriginalScope {
+    // the scopes of `scopes` and their descendants in pre-order
+    pub fn flatten(scopes: &[OriginalScope]) -> Vec<&OriginalScope> {
+        let mut result = Vec::new();
+        let mut stack: Vec<&OriginalScope> = scopes.iter().rev().collect();
+        while let Some(scope) = stack.pop() {
+            result.push(scope);
+            stack.extend(scope.children.iter().rev());
+        }
+        result
+    }
+
+    // for each scope in the order of `flatten`, the index of its parent there and its
+    // position among its siblings
+    pub(crate) fn parents(scopes: &[OriginalScope]) -> Vec<(Option<usize>, usize)> {
+        let mut result = Vec::new();
+        let mut stack: Vec<(Option<usize>, usize, &OriginalScope)> =
+            scopes.iter().enumerate().rev().map(|(pos, scope)| (None, pos, scope)).collect();
+        while let Some((parent, pos, scope)) = stack.pop() {
+            let idx = result.len();
+            result.push((parent, pos));
+            stack.extend(scope.children.iter().enumerate().rev().map(|(pos, child)| (Some(idx), pos, child)));
+        }
+        result
+    }
+
+    pub fn contains(&self, position: (usize, usize)) -> bool {
+        self.start <= position && position < self.end
+    }
+
+    pub(crate) fn collect_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        names.extend(self.name.iter().map(|name| name.as_str()));
+        names.extend(self.kind.iter().map(|kind| kind.as_str()));
+        names.extend(self.variables.iter().map(|variable| variable.as_str()));
+        for child in &self.children {
+            child.collect_names(names);
+        }
+    }
+}
+
+impl GeneratedRange {
+    pub fn contains(&self, position: (usize, usize)) -> bool {
+        self.start <= position && position < self.end
+    }
+
+    pub(crate) fn collect_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        names.extend(self.bindings.iter().filter_map(|binding| binding.as_ref().map(|b| b.as_str())));
+        for child in &self.children {
+            child.collect_names(names);
+        }
+    }
+}
+
+// Each source's scopes are a list of `,` separated items, a start item for each scope
+// followed by its children and an end item:
+//   start: line, column, flags, [name], [kind], variables...
+//   end:   line, column
+// lines are relative to the previous item, names are indices relative to the previous
+// name of the source.
+pub(crate) fn encode_original_scopes<F>(scopes: &[OriginalScope], name_index: &F) -> String
+where
+    F: Fn(&str) -> usize,
+{
+    fn encode<F: Fn(&str) -> usize>(scope: &OriginalScope, name_index: &F, state: &mut [i64; 2], buf: &mut Vec<u8>) {
+        item(buf, b',');
+        // lines are stored 0-based in SourceMap spec version 3
+        push_vlq(buf, relative(&mut state[0], scope.start.0 as i64 - 1));
+        push_vlq(buf, scope.start.1 as i64);
+        let mut flags = 0;
+        if scope.name.is_some() {
+            flags |= SCOPE_HAS_NAME;
+        }
+        if scope.kind.is_some() {
+            flags |= SCOPE_HAS_KIND;
+        }
+        if scope.is_stack_frame {
+            flags |= SCOPE_IS_STACK_FRAME;
+        }
+        push_vlq(buf, flags);
+        for name in scope.name.iter().chain(scope.kind.iter()).chain(scope.variables.iter()) {
+            push_vlq(buf, relative(&mut state[1], name_index(name) as i64));
+        }
+        for child in &scope.children {
+            encode(child, name_index, state, buf);
+        }
+        item(buf, b',');
+        push_vlq(buf, relative(&mut state[0], scope.end.0 as i64 - 1));
+        push_vlq(buf, scope.end.1 as i64);
+    }
+
+    // previous line and name index
+    let mut state = [0; 2];
+    let mut buf = Vec::new();
+    for scope in scopes {
+        encode(scope, name_index, &mut state, &mut buf);
+    }
+    String::from_utf8(buf).unwrap()
+}
+
+pub(crate) fn decode_original_scopes(encoded: &str, names: &[&str]) -> Result<Vec<OriginalScope>, Error> {
+    let mut line = 0;
+    let mut name = 0;
+    let mut stack: Vec<OriginalScope> = Vec::new();
+    let mut scopes = Vec::new();
+    for (offset, fields) in items(encoded)? {
+        let invalid = || Error::InvalidScopes(offset);
+        line += *fields.first().ok_or_else(invalid)?;
+        let position = (to_index(line).ok_or_else(invalid)? + 1, to_index(*fields.get(1).ok_or_else(invalid)?).ok_or_else(invalid)?);
+        if fields.len() == 2 {
+            let mut scope = stack.pop().ok_or_else(invalid)?;
+            scope.end = position;
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(scope),
+                None => scopes.push(scope),
+            }
+            continue;
+        }
+
+        let flags = fields[2];
+        let mut rest = fields[3..].iter().map(|&delta| {
+            name += delta;
+            to_index(name)
+                .ok_or_else(invalid)
+                .and_then(|idx| names.get(idx).map(|s| String::from(*s)).ok_or(Error::NameOutOfRange(idx)))
+        });
+        let scope_name = if flags & SCOPE_HAS_NAME != 0 { Some(rest.next().ok_or_else(invalid)??) } else { None };
+        let kind = if flags & SCOPE_HAS_KIND != 0 { Some(rest.next().ok_or_else(invalid)??) } else { None };
+        let variables = rest.collect::<Result<_, Error>>()?;
+        stack.push(OriginalScope {
+            start: position,
+            end: position,
+            name: scope_name,
+            kind,
+            is_stack_frame: flags & SCOPE_IS_STACK_FRAME != 0,
+            variables,
+            children: Vec::new(),
+        });
+    }
+    if !stack.is_empty() {
+        return Err(Error::InvalidScopes(encoded.len()));
+    }
+    Ok(scopes)
+}
+
+// Generated ranges are `;` separated generated lines of `,` separated items, a start
+// item for each range followed by its children and an end item:
+//   start: column, flags, [definition source, definition index],
+//          [call site source, call site line, call site column], bindings...
+//   end:   column
+// columns are relative to the previous item of the line, definitions and call sites to
+// the previous ones, and bindings are absolute name indices plus one, zero meaning the
+// variable is unavailable.
+pub(crate) fn encode_generated_ranges<F, G>(ranges: &[GeneratedRange], source_index: &F, name_index: &G) -> Result<String, Error>
+where
+    F: Fn(&str) -> Option<usize>,
+    G: Fn(&str) -> usize,
+{
+    struct State {
+        // (line, column) of the previous item
+        position: (usize, usize),
+        definition: [i64; 2],
+        call_site: [i64; 3],
+        buf: Vec<u8>,
+    }
+
+    impl State {
+        fn item(&mut self, position: (usize, usize)) {
+            if position.0 > self.position.0 {
+                self.buf.resize(self.buf.len() + position.0 - self.position.0, b';');
+                self.position = (position.0, 0);
+            } else {
+                item(&mut self.buf, b',');
+            }
+            push_vlq(&mut self.buf, position.1 as i64 - self.position.1 as i64);
+            self.position = position;
+        }
+    }
+
+    fn encode<F, G>(range: &GeneratedRange, source_index: &F, name_index: &G, state: &mut State) -> Result<(), Error>
+    where
+        F: Fn(&str) -> Option<usize>,
+        G: Fn(&str) -> usize,
+    {
+        if range.start < state.position || range.end < range.start || range.start.0 == 0 {
+            return Err(Error::InvalidScopes(state.buf.len()));
+        }
+        let source = |source: &str| source_index(source).ok_or(Error::InvalidScopes(state.buf.len()));
+        let definition = match range.definition {
+            Some(ref definition) => Some((source(&definition.source)?, definition.index)),
+            None => None,
+        };
+        let call_site = match range.call_site {
+            Some(ref call_site) if call_site.line > 0 => Some((source(&call_site.source)?, call_site)),
+            Some(_) => return Err(Error::InvalidScopes(state.buf.len())),
+            None => None,
+        };
+
+        state.item(range.start);
+        let mut flags = 0;
+        if definition.is_some() {
+            flags |= RANGE_HAS_DEFINITION;
+        }
+        if call_site.is_some() {
+            flags |= RANGE_HAS_CALL_SITE;
+        }
+        if range.is_stack_frame {
+            flags |= RANGE_IS_STACK_FRAME;
+        }
+        if range.is_hidden {
+            flags |= RANGE_IS_HIDDEN;
+        }
+        push_vlq(&mut state.buf, flags);
+        if let Some((source, index)) = definition {
+            let delta = relative(&mut state.definition[0], source as i64);
+            push_vlq(&mut state.buf, delta);
+            let delta = relative(&mut state.definition[1], index as i64);
+            push_vlq(&mut state.buf, delta);
+        }
+        if let Some((source, call_site)) = call_site {
+            // lines are stored 0-based in SourceMap spec version 3
+            let position = [source as i64, call_site.line as i64 - 1, call_site.column as i64];
+            for (previous, value) in state.call_site.iter_mut().zip(position.iter()) {
+                let delta = relative(previous, *value);
+                push_vlq(&mut state.buf, delta);
+            }
+        }
+        for binding in &range.bindings {
+            let value = binding.as_ref().map_or(0, |binding| name_index(binding) + 1);
+            push_vlq(&mut state.buf, value as i64);
+        }
+
+        for child in &range.children {
+            encode(child, source_index, name_index, state)?;
+        }
+        if range.end < state.position {
+            return Err(Error::InvalidScopes(state.buf.len()));
+        }
+        state.item(range.end);
+        Ok(())
+    }
+
+    let mut state = State {
+        position: (1, 0),
+        definition: [0; 2],
+        call_site: [0; 3],
+        buf: Vec::new(),
+    };
+    for range in ranges {
+        encode(range, source_index, name_index, &mut state)?;
+    }
+    Ok(String::from_utf8(state.buf).unwrap())
+}
+
+pub(crate) fn decode_generated_ranges(encoded: &str, sources: &[&str], names: &[&str]) -> Result<Vec<GeneratedRange>, Error> {
+    let mut definition: [i64; 2] = [0; 2];
+    let mut call_site: [i64; 3] = [0; 3];
+    let mut stack: Vec<GeneratedRange> = Vec::new();
+    let mut ranges = Vec::new();
+    let mut line_offset = 0;
+    for (line, line_str) in encoded.split(';').enumerate() {
+        let mut column = 0;
+        for (offset, fields) in items(line_str)? {
+            let offset = line_offset + offset;
+            let invalid = || Error::InvalidScopes(offset);
+            column += *fields.first().ok_or_else(invalid)?;
+            let position = (line + 1, to_index(column).ok_or_else(invalid)?);
+            if fields.len() == 1 {
+                let mut range = stack.pop().ok_or_else(invalid)?;
+                range.end = position;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(range),
+                    None => ranges.push(range),
+                }
+                continue;
+            }
+
+            let flags = fields[1];
+            let mut rest = fields[2..].iter().cloned();
+            let source = |idx: i64| {
+                let idx = to_index(idx).ok_or_else(invalid)?;
+                sources.get(idx).map(|s| String::from(*s)).ok_or(Error::SourceOutOfRange(idx))
+            };
+            let range_definition = if flags & RANGE_HAS_DEFINITION != 0 {
+                for value in definition.iter_mut() {
+                    *value += rest.next().ok_or_else(invalid)?;
+                }
+                Some(ScopeDefinition {
+                    source: source(definition[0])?,
+                    index: to_index(definition[1]).ok_or_else(invalid)?,
+                })
+            } else {
+                None
+            };
+            let range_call_site = if flags & RANGE_HAS_CALL_SITE != 0 {
+                for value in call_site.iter_mut() {
+                    *value += rest.next().ok_or_else(invalid)?;
+                }
+                Some(CallSite {
+                    source: source(call_site[0])?,
+                    line: to_index(call_site[1]).ok_or_else(invalid)? + 1,
+                    column: to_index(call_site[2]).ok_or_else(invalid)?,
+                })
+            } else {
+                None
+            };
+            let bindings = rest
+                .map(|value| match to_index(value).ok_or_else(invalid)? {
+                    0 => Ok(None),
+                    idx => names.get(idx - 1).map(|s| Some(String::from(*s))).ok_or(Error::NameOutOfRange(idx - 1)),
+                })
+                .collect::<Result<_, Error>>()?;
+            stack.push(GeneratedRange {
+                start: position,
+                end: position,
+                definition: range_definition,
+                call_site: range_call_site,
+                is_stack_frame: flags & RANGE_IS_STACK_FRAME != 0,
+                is_hidden: flags & RANGE_IS_HIDDEN != 0,
+                bindings,
+                children: Vec::new(),
+            });
+        }
+        line_offset += line_str.len() + 1;
+    }
+    if !stack.is_empty() {
+        return Err(Error::InvalidScopes(encoded.len()));
+    }
+    Ok(ranges)
+}
+
+fn item(buf: &mut Vec<u8>, separator: u8) {
+    if !buf.is_empty() && buf[buf.len() - 1] != b';' {
+        buf.push(separator);
+    }
+}
+
+fn push_vlq(buf: &mut Vec<u8>, value: i64) {
+    vlq::encode(value, buf).unwrap();
+}
+
+fn relative(previous: &mut i64, value: i64) -> i64 {
+    let delta = value - *previous;
+    *previous = value;
+    delta
+}
+
+fn to_index(value: i64) -> Option<usize> {
+    if value < 0 {
+        None
+    } else {
+        Some(value as usize)
+    }
+}
+
+// the VLQ fields of each `,` separated item along with its byte offset
+fn items(encoded: &str) -> Result<Vec<(usize, Vec<i64>)>, Error> {
+    let mut result = Vec::new();
+    let mut offset = 0;
+    for item in encoded.split(',') {
+        let bytes = item.as_bytes();
+        let mut fields = Vec::new();
+        let mut input = bytes.iter().cloned().peekable();
+        while input.peek().is_some() {
+            fields.push(vlq::decode(&mut input).map_err(|_| Error::InvalidScopes(offset))?);
+        }
+        if !fields.is_empty() {
+            result.push((offset, fields));
+        }
+        offset += bytes.len() + 1;
+    }
+    Ok(result)
+}
@@ -1,3 +1,8 @@
+use error::Error;
+use serde::{Deserialize, Deserializer};
+use serde_json;
+use std::borrow::Cow;
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct StringWithSrcMap {
     pub source: String,
@@ -5,12 +10,272 @@ pub struct StringWithSrcMap {
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SrcMap {
     pub version: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub source_root: Option<String>,
     pub sources: Vec<String>,
-    pub sources_content: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources_content: Vec<Option<String>>,
+    #[serde(default)]
     pub names: Vec<String>,
     pub mappings: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range_mappings: Option<String>,
+    // encoded scopes of each source, aligned with `sources`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub original_scopes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated_ranges: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore_list: Vec<usize>,
+    // superseded by `ignoreList`, only read when that one is absent
+    #[serde(default, rename = "x_google_ignoreList", skip_serializing_if = "Vec::is_empty")]
+    pub x_google_ignore_list: Vec<usize>,
+    #[serde(default, alias = "debug_id", skip_serializing_if = "Option::is_none")]
+    pub debug_id: Option<String>,
+}
+
+impl Default for SrcMap {
+    fn default() -> SrcMap {
+        SrcMap {
+            version: 3,
+            file: None,
+            source_root: None,
+            sources: Vec::new(),
+            sources_content: Vec::new(),
+            names: Vec::new(),
+            mappings: String::new(),
+            range_mappings: None,
+            original_scopes: Vec::new(),
+            generated_ranges: None,
+            ignore_list: Vec::new(),
+            x_google_ignore_list: Vec::new(),
+            debug_id: None,
+        }
+    }
+}
+
+impl SrcMap {
+    pub fn from_json(json: &str) -> Result<SrcMap, Error> {
+        let source_map = SrcMap::from_json_lenient(json)?;
+        if source_map.version != 3 {
+            return Err(Error::UnsupportedVersion(source_map.version));
+        }
+        Ok(source_map)
+    }
+
+    // accepts any `version`, for maps from tools that emit a wrong one
+    pub fn from_json_lenient(json: &str) -> Result<SrcMap, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn ignored_indices(&self) -> &[usize] {
+        if self.ignore_list.is_empty() {
+            &self.x_google_ignore_list
+        } else {
+            &self.ignore_list
+        }
+    }
+}
+
+// borrowed counterpart of `SrcMap`, strings without escapes are sliced from the input
+// instead of allocated; `LazySourceMapConsumer` keeps them borrowed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SrcMapRef<'a> {
+    pub version: i32,
+    #[serde(borrow, default, deserialize_with = "borrow_option", skip_serializing_if = "Option::is_none")]
+    pub file: Option<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "borrow_option", skip_serializing_if = "Option::is_none")]
+    pub source_root: Option<Cow<'a, str>>,
+    #[serde(borrow, deserialize_with = "borrow_vec")]
+    pub sources: Vec<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "borrow_option_vec", skip_serializing_if = "Vec::is_empty")]
+    pub sources_content: Vec<Option<Cow<'a, str>>>,
+    #[serde(borrow, default, deserialize_with = "borrow_vec")]
+    pub names: Vec<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub mappings: Cow<'a, str>,
+    #[serde(borrow, default, deserialize_with = "borrow_option", skip_serializing_if = "Option::is_none")]
+    pub range_mappings: Option<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "borrow_vec", skip_serializing_if = "Vec::is_empty")]
+    pub original_scopes: Vec<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "borrow_option", skip_serializing_if = "Option::is_none")]
+    pub generated_ranges: Option<Cow<'a, str>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore_list: Vec<usize>,
+    #[serde(default, rename = "x_google_ignoreList", skip_serializing_if = "Vec::is_empty")]
+    pub x_google_ignore_list: Vec<usize>,
+    #[serde(borrow, default, alias = "debug_id", deserialize_with = "borrow_option", skip_serializing_if = "Option::is_none")]
+    pub debug_id: Option<Cow<'a, str>>,
+}
+
+impl<'a> SrcMapRef<'a> {
+    pub fn from_json(json: &'a str) -> Result<SrcMapRef<'a>, Error> {
+        let source_map = SrcMapRef::from_json_lenient(json)?;
+        if source_map.version != 3 {
+            return Err(Error::UnsupportedVersion(source_map.version));
+        }
+        Ok(source_map)
+    }
+
+    pub fn from_json_lenient(json: &'a str) -> Result<SrcMapRef<'a>, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn ignored_indices(&self) -> &[usize] {
+        if self.ignore_list.is_empty() {
+            &self.x_google_ignore_list
+        } else {
+            &self.ignore_list
+        }
+    }
+
+    pub fn into_owned(self) -> SrcMap {
+        SrcMap {
+            version: self.version,
+            file: self.file.map(Cow::into_owned),
+            source_root: self.source_root.map(Cow::into_owned),
+            sources: self.sources.into_iter().map(Cow::into_owned).collect(),
+            sources_content: self
+                .sources_content
+                .into_iter()
+                .map(|content| content.map(Cow::into_owned))
+                .collect(),
+            names: self.names.into_iter().map(Cow::into_owned).collect(),
+            mappings: self.mappings.into_owned(),
+            range_mappings: self.range_mappings.map(Cow::into_owned),
+            original_scopes: self.original_scopes.into_iter().map(Cow::into_owned).collect(),
+            generated_ranges: self.generated_ranges.map(Cow::into_owned),
+            ignore_list: self.ignore_list,
+            x_google_ignore_list: self.x_google_ignore_list,
+            debug_id: self.debug_id.map(Cow::into_owned),
+        }
+    }
+}
+
+impl<'a> From<&'a SrcMap> for SrcMapRef<'a> {
+    fn from(source_map: &'a SrcMap) -> SrcMapRef<'a> {
+        let borrow = |s: &'a String| Cow::Borrowed(s.as_str());
+        SrcMapRef {
+            version: source_map.version,
+            file: source_map.file.as_ref().map(borrow),
+            source_root: source_map.source_root.as_ref().map(borrow),
+            sources: source_map.sources.iter().map(borrow).collect(),
+            sources_content: source_map
+                .sources_content
+                .iter()
+                .map(|content| content.as_ref().map(borrow))
+                .collect(),
+            names: source_map.names.iter().map(borrow).collect(),
+            mappings: borrow(&source_map.mappings),
+            range_mappings: source_map.range_mappings.as_ref().map(borrow),
+            original_scopes: source_map.original_scopes.iter().map(borrow).collect(),
+            generated_ranges: source_map.generated_ranges.as_ref().map(borrow),
+            ignore_list: source_map.ignore_list.clone(),
+            x_google_ignore_list: source_map.x_google_ignore_list.clone(),
+            debug_id: source_map.debug_id.as_ref().map(borrow),
+        }
+    }
+}
+
+impl From<SrcMap> for SrcMapRef<'static> {
+    fn from(source_map: SrcMap) -> SrcMapRef<'static> {
+        SrcMapRef {
+            version: source_map.version,
+            file: source_map.file.map(Cow::Owned),
+            source_root: source_map.source_root.map(Cow::Owned),
+            sources: source_map.sources.into_iter().map(Cow::Owned).collect(),
+            sources_content: source_map
+                .sources_content
+                .into_iter()
+                .map(|content| content.map(Cow::Owned))
+                .collect(),
+            names: source_map.names.into_iter().map(Cow::Owned).collect(),
+            mappings: Cow::Owned(source_map.mappings),
+            range_mappings: source_map.range_mappings.map(Cow::Owned),
+            original_scopes: source_map.original_scopes.into_iter().map(Cow::Owned).collect(),
+            generated_ranges: source_map.generated_ranges.map(Cow::Owned),
+            ignore_list: source_map.ignore_list,
+            x_google_ignore_list: source_map.x_google_ignore_list,
+            debug_id: source_map.debug_id.map(Cow::Owned),
+        }
+    }
+}
+
+// serde only borrows a `Cow` that is the field itself, nested ones go through this wrapper
+#[derive(Deserialize)]
+struct Borrowed<'a>(#[serde(borrow)] Cow<'a, str>);
+
+fn borrow_option<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Cow<'de, str>>, D::Error> {
+    let value: Option<Borrowed<'de>> = Deserialize::deserialize(deserializer)?;
+    Ok(value.map(|s| s.0))
+}
+
+fn borrow_vec<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Cow<'de, str>>, D::Error> {
+    let value: Vec<Borrowed<'de>> = Deserialize::deserialize(deserializer)?;
+    Ok(value.into_iter().map(|s| s.0).collect())
+}
+
+fn borrow_option_vec<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Option<Cow<'de, str>>>, D::Error> {
+    let value: Vec<Option<Borrowed<'de>>> = Deserialize::deserialize(deserializer)?;
+    Ok(value.into_iter().map(|content| content.map(|s| s.0)).collect())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexSrcMap {
+    pub version: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    pub sections: Vec<Section>,
+}
+
+impl IndexSrcMap {
+    pub fn from_json(json: &str) -> Result<IndexSrcMap, Error> {
+        let source_map = IndexSrcMap::from_json_lenient(json)?;
+        let versions = Some(source_map.version)
+            .into_iter()
+            .chain(source_map.sections.iter().map(|section| section.map.version));
+        for version in versions {
+            if version != 3 {
+                return Err(Error::UnsupportedVersion(version));
+            }
+        }
+        Ok(source_map)
+    }
+
+    pub fn from_json_lenient(json: &str) -> Result<IndexSrcMap, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Section {
+    pub offset: Offset,
+    pub map: SrcMap,
+}
+
+// lines and columns are both 0-based, as stored in SourceMap spec version 3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Offset {
+    pub line: usize,
+    pub column: usize,
 }
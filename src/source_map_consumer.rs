@@ -0,0 +1,535 @@
+use column_unit::{convert_column, ColumnUnit};
+use error::Error;
+use line_terminators::LineTerminators;
+use mapping::Mapping;
+use scopes::{self, GeneratedRange, OriginalScope};
+use source_map::SrcMap;
+use std::collections::{HashMap, HashSet};
+use utils;
+use Ptr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bias {
+    GreatestLowerBound,
+    LeastUpperBound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappingOrder {
+    Generated,
+    Original,
+}
+
+#[derive(Debug)]
+pub struct SourceMapConsumer {
+    file: Option<Ptr<String>>,
+    source_root: Option<Ptr<String>>,
+    debug_id: Option<Ptr<String>>,
+    sources: Vec<Ptr<String>>,
+    source_indices: HashMap<Ptr<String>, usize>,
+    names: Vec<Ptr<String>>,
+    sources_content: Vec<Option<Ptr<String>>>,
+    ignore_list: HashSet<usize>,
+    generated_mappings: Vec<Mapping>,
+    original_mappings: Vec<Mapping>,
+    // aligned with `sources`
+    original_scopes: Vec<Vec<OriginalScope>>,
+    // `OriginalScope::parents` of each entry of `original_scopes`
+    scope_parents: Vec<Vec<(Option<usize>, usize)>>,
+    generated_ranges: Vec<GeneratedRange>,
+    column_unit: ColumnUnit,
+    line_terminators: LineTerminators,
+}
+
+impl SourceMapConsumer {
+    // `version` is only checked by `from_json`, so maps read with `SrcMap::from_json_lenient` work
+    pub fn new(source_map: SrcMap) -> Result<SourceMapConsumer, Error> {
+        let mut ignore_list = HashSet::new();
+        for &idx in source_map.ignored_indices() {
+            if idx >= source_map.sources.len() {
+                return Err(Error::SourceOutOfRange(idx));
+            }
+            ignore_list.insert(idx);
+        }
+
+        let file = source_map.file.map(Ptr::new);
+        let source_root = source_map.source_root.map(Ptr::new);
+        let debug_id = source_map.debug_id.map(Ptr::new);
+
+        let (sources, source_indices) = resolve_sources(&source_root, &source_map.sources);
+        let names: Vec<Ptr<String>> = source_map.names.into_iter().map(Ptr::new).collect();
+        let source_strs: Vec<&str> = sources.iter().map(|source| source.as_str()).collect();
+        let name_strs: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+        let mut original_scopes = source_map
+            .original_scopes
+            .iter()
+            .map(|encoded| scopes::decode_original_scopes(encoded, &name_strs))
+            .collect::<Result<Vec<_>, Error>>()?;
+        original_scopes.resize(sources.len(), Vec::new());
+        let scope_parents = original_scopes.iter().map(|scopes| OriginalScope::parents(scopes)).collect();
+        let generated_ranges = match source_map.generated_ranges {
+            Some(ref encoded) => scopes::decode_generated_ranges(encoded, &source_strs, &name_strs)?,
+            None => Vec::new(),
+        };
+        let sources_content: Vec<Option<Ptr<String>>> = source_map
+            .sources_content
+            .into_iter()
+            .map(|content| content.map(Ptr::new))
+            .collect();
+
+        let mappings = utils::parse_mappings(&source_map.mappings)?;
+        let range_segments = match source_map.range_mappings {
+            Some(ref range_mappings) => {
+                utils::range_segments(&source_map.mappings, &utils::parse_range_mappings(range_mappings)?)?
+            }
+            None => HashSet::new(),
+        };
+        let generated_mappings: Vec<Mapping> = mappings
+            .by_generated_location()
+            .iter()
+            .map(|mapping| {
+                let line = mapping.generated_line as usize;
+                let segment = utils::segment(mapping);
+                let range = range_segments.contains(&(line, segment));
+                decoded_mapping((line, segment.0), segment.1, range, &sources, &names)
+            })
+            .collect::<Result<_, Error>>()?;
+        let mut original_mappings: Vec<Mapping> = generated_mappings
+            .iter()
+            .filter(|mapping| mapping.source.is_some())
+            .cloned()
+            .collect();
+        original_mappings.sort_by(|a, b| a.cmp_by_original(b));
+
+        Ok(SourceMapConsumer {
+            file,
+            source_root,
+            debug_id,
+            sources,
+            source_indices,
+            names,
+            sources_content,
+            ignore_list,
+            generated_mappings,
+            original_mappings,
+            original_scopes,
+            scope_parents,
+            generated_ranges,
+            column_unit: ColumnUnit::default(),
+            line_terminators: LineTerminators::default(),
+        })
+    }
+
+    pub fn from_json(source_map: &str) -> Result<SourceMapConsumer, Error> {
+        SourceMapConsumer::new(SrcMap::from_json(source_map)?)
+    }
+
+    pub fn file(&self) -> Option<&Ptr<String>> {
+        self.file.as_ref()
+    }
+
+    pub fn source_root(&self) -> Option<&Ptr<String>> {
+        self.source_root.as_ref()
+    }
+
+    pub fn debug_id(&self) -> Option<&Ptr<String>> {
+        self.debug_id.as_ref()
+    }
+
+    // the unit the columns of the map are counted in
+    pub fn column_unit(&self) -> ColumnUnit {
+        self.column_unit
+    }
+
+    pub fn set_column_unit(&mut self, column_unit: ColumnUnit) {
+        self.column_unit = column_unit;
+    }
+
+    // the line terminators of the generated code and of the source contents
+    pub fn line_terminators(&self) -> LineTerminators {
+        self.line_terminators
+    }
+
+    pub fn set_line_terminators(&mut self, line_terminators: LineTerminators) {
+        self.line_terminators = line_terminators;
+    }
+
+    pub fn sources(&self) -> &[Ptr<String>] {
+        &self.sources
+    }
+
+    pub fn names(&self) -> &[Ptr<String>] {
+        &self.names
+    }
+
+    pub fn has_contents_of_all_sources(&self) -> bool {
+        self.sources_content.len() >= self.sources.len()
+            && self.sources_content.iter().all(|content| content.is_some())
+    }
+
+    pub fn source_content_for(&self, source: &str) -> Option<Ptr<String>> {
+        self.source_index(source)
+            .and_then(|idx| self.sources_content.get(idx).cloned())
+            .and_then(|content| content)
+    }
+
+    pub fn is_ignored(&self, source: &str) -> bool {
+        self.source_index(source)
+            .is_some_and(|idx| self.ignore_list.contains(&idx))
+    }
+
+    pub fn original_scopes(&self, source: &str) -> &[OriginalScope] {
+        self.source_index(source)
+            .and_then(|idx| self.original_scopes.get(idx))
+            .map_or(&[], |scopes| scopes)
+    }
+
+    pub fn generated_ranges(&self) -> &[GeneratedRange] {
+        &self.generated_ranges
+    }
+
+    // the generated ranges containing a generated position that were generated from an
+    // original scope, along with that scope and the original scopes enclosing it, all
+    // innermost first; inlined function bodies show up as ranges with a call site
+    pub fn scopes_at(&self, line: usize, column: usize) -> Vec<(&GeneratedRange, Vec<&OriginalScope>)> {
+        let mut result = Vec::new();
+        let mut ranges = &self.generated_ranges[..];
+        while let Some(range) = ranges.iter().find(|range| range.contains((line, column))) {
+            let chain = range.definition.as_ref().and_then(|definition| {
+                let idx = self.source_index(&definition.source)?;
+                self.scope_chain(idx, definition.index)
+            });
+            if let Some(chain) = chain {
+                result.push((range, chain));
+            }
+            ranges = &range.children;
+        }
+        result.reverse();
+        result
+    }
+
+    // the `index`-th scope of a source in pre-order followed by its ancestors
+    fn scope_chain(&self, source_idx: usize, index: usize) -> Option<Vec<&OriginalScope>> {
+        let parents = &self.scope_parents[source_idx];
+        let mut positions = Vec::new();
+        let mut idx = Some(index);
+        while let Some(i) = idx {
+            let &(parent, pos) = parents.get(i)?;
+            positions.push(pos);
+            idx = parent;
+        }
+        let mut chain = Vec::new();
+        let mut scopes = &self.original_scopes[source_idx][..];
+        for &pos in positions.iter().rev() {
+            let scope = &scopes[pos];
+            chain.push(scope);
+            scopes = &scope.children;
+        }
+        chain.reverse();
+        Some(chain)
+    }
+
+    fn source_index(&self, source: &str) -> Option<usize> {
+        source_index(&self.source_indices, &self.source_root, source)
+    }
+
+    // originate from `SourceMapConsumer.OriginalPositionFor`
+    pub fn original_position_for(&self, line: usize, column: usize, bias: Bias) -> Mapping {
+        original_position_in(&self.generated_mappings, line, column, bias)
+    }
+
+    // originate from `SourceMapConsumer.generatedPositionFor`
+    pub fn generated_position_for(
+        &self,
+        source: &str,
+        line: usize,
+        column: usize,
+        bias: Bias,
+    ) -> Mapping {
+        if let Some(source_idx) = self.source_index(source) {
+            let source = Some(self.sources[source_idx].clone());
+            if let Some(mapping) = self.range_mapping_covering_original(&source, line, column) {
+                return mapping;
+            }
+            let idx = utils::binary_search(
+                &self.original_mappings,
+                &(source.clone(), Some((line, column))),
+                |mapping| (mapping.source.clone(), mapping.original),
+                bias,
+            );
+            if let Some(idx) = idx {
+                let mapping = &self.original_mappings[idx];
+                if mapping.source == source {
+                    return mapping.clone();
+                }
+            }
+        }
+        Mapping {
+            generated: (0, 0),
+            source: None,
+            name: None,
+            original: None,
+            range: false,
+        }
+    }
+
+    // like `original_position_for`, with `column` and the returned columns counted in
+    // `column_unit`; `generated_line` is the text of generated line `line`, the original
+    // column is only converted when the content of its source is known
+    pub fn original_position_for_in(
+        &self,
+        generated_line: &str,
+        line: usize,
+        column: usize,
+        column_unit: ColumnUnit,
+        bias: Bias,
+    ) -> Mapping {
+        let column = convert_column(generated_line, column, column_unit, self.column_unit);
+        let mapping = self.original_position_for(line, column, bias);
+        convert_mapping(mapping, generated_line, self.column_unit, column_unit, |source, line| {
+            self.source_line(source, line)
+        })
+    }
+
+    // like `generated_position_for`, with the original columns counted in `column_unit`,
+    // which needs the content of `source`; the returned generated column is left in the
+    // unit of the map since the generated code is unknown here
+    pub fn generated_position_for_in(
+        &self,
+        source: &str,
+        line: usize,
+        column: usize,
+        column_unit: ColumnUnit,
+        bias: Bias,
+    ) -> Mapping {
+        let original_line = self.source_line(source, line).unwrap_or("");
+        let column = convert_column(original_line, column, column_unit, self.column_unit);
+        let mut mapping = self.generated_position_for(source, line, column, bias);
+        // the bias may have led to another line
+        if let Some((line, column)) = mapping.original {
+            let original_line = self.source_line(source, line).unwrap_or("");
+            let column = convert_column(original_line, column, self.column_unit, column_unit);
+            mapping.original = Some((line, column));
+        }
+        mapping
+    }
+
+    // text of `line` in the content of `source`, if known
+    fn source_line(&self, source: &str, line: usize) -> Option<&str> {
+        let idx = self.source_index(source)?;
+        content_line(self.sources_content.get(idx)?.as_ref()?, self.line_terminators, line)
+    }
+
+    fn range_mapping_covering_original(
+        &self,
+        source: &Option<Ptr<String>>,
+        line: usize,
+        column: usize,
+    ) -> Option<Mapping> {
+        let idx = utils::binary_search(
+            &self.original_mappings,
+            &(source.clone(), Some((line, column))),
+            |mapping| (mapping.source.clone(), mapping.original),
+            Bias::GreatestLowerBound,
+        )?;
+        let mapping = &self.original_mappings[idx];
+        let original = mapping.original?;
+        if !mapping.range || mapping.source != *source || original.0 != line || original.1 >= column {
+            return None;
+        }
+        // the range ends at the next mapping of the generated line
+        let generated = (mapping.generated.0, mapping.generated.1 + column - original.1);
+        let next = utils::binary_search(
+            &self.generated_mappings,
+            &(mapping.generated.0, mapping.generated.1 + 1),
+            |mapping| mapping.generated,
+            Bias::LeastUpperBound,
+        );
+        match next.map(|idx| &self.generated_mappings[idx]) {
+            Some(next) if next.generated.0 == generated.0 && next.generated.1 <= generated.1 => None,
+            _ => Some(mapping.at_column(generated.1)),
+        }
+    }
+
+    // originate from `SourceMapConsumer.allGeneratedPositionsFor`, when `column` is `None`
+    // every mapping on the closest original line having any mappings is returned
+    pub fn all_generated_positions_for(
+        &self,
+        source: &str,
+        line: usize,
+        column: Option<usize>,
+    ) -> Vec<Mapping> {
+        let source_idx = match self.source_index(source) {
+            Some(idx) => idx,
+            None => return Vec::new(),
+        };
+        let source = Some(self.sources[source_idx].clone());
+        let idx = utils::binary_search(
+            &self.original_mappings,
+            &(source.clone(), Some((line, column.unwrap_or(0)))),
+            |mapping| (mapping.source.clone(), mapping.original),
+            Bias::LeastUpperBound,
+        );
+
+        let mut mappings = Vec::new();
+        if let Some(idx) = idx {
+            let found = self.original_mappings[idx].original;
+            let found_line = found.map(|original| original.0);
+            let matches = |mapping: &&Mapping| {
+                mapping.source == source && match column {
+                    None => mapping.original.map(|original| original.0) == found_line,
+                    Some(_) => mapping.original == found && found_line == Some(line),
+                }
+            };
+            mappings.extend(self.original_mappings[idx..].iter().take_while(matches).cloned());
+        }
+        mappings
+    }
+
+    pub fn each_mapping<F>(&self, order: MappingOrder, mut f: F)
+    where
+        F: FnMut(&Mapping),
+    {
+        match order {
+            MappingOrder::Generated => {
+                for mapping in &self.generated_mappings {
+                    f(mapping);
+                }
+            }
+            MappingOrder::Original => {
+                for mapping in &self.original_mappings {
+                    f(mapping);
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn resolve_source(source_root: &Option<Ptr<String>>, source: &str) -> String {
+    match *source_root {
+        Some(ref root) if !root.is_empty() => utils::join(root, source),
+        _ => utils::normalize(source),
+    }
+}
+
+// the sources of a map resolved against its source root, and the index of each
+pub(crate) fn resolve_sources<S: AsRef<str>>(
+    source_root: &Option<Ptr<String>>,
+    sources: &[S],
+) -> (Vec<Ptr<String>>, HashMap<Ptr<String>, usize>) {
+    let sources: Vec<Ptr<String>> = sources
+        .iter()
+        .map(|source| Ptr::new(resolve_source(source_root, source.as_ref())))
+        .collect();
+    let mut source_indices = HashMap::new();
+    for (idx, source) in sources.iter().enumerate() {
+        source_indices.entry(source.clone()).or_insert(idx);
+    }
+    (sources, source_indices)
+}
+
+// `source` may be given as resolved or relative to the source root
+pub(crate) fn source_index(
+    source_indices: &HashMap<Ptr<String>, usize>,
+    source_root: &Option<Ptr<String>>,
+    source: &str,
+) -> Option<usize> {
+    let idx = source_indices.get(&String::from(source)).or_else(|| {
+        source_root
+            .as_ref()
+            .and_then(|root| source_indices.get(&utils::join(root, &utils::normalize(source))))
+    });
+    idx.cloned()
+}
+
+// a decoded segment with 0-based lines as stored, `original` being the source index,
+// original line, original column and name index
+pub(crate) fn decoded_mapping(
+    generated: (usize, usize),
+    original: Option<(usize, usize, usize, Option<usize>)>,
+    range: bool,
+    sources: &[Ptr<String>],
+    names: &[Ptr<String>],
+) -> Result<Mapping, Error> {
+    // lines are stored 0-based in SourceMap spec version 3
+    let generated = (generated.0 + 1, generated.1);
+    let (source_idx, line, column, name_idx) = match original {
+        Some(original) => original,
+        None => {
+            return Ok(Mapping {
+                generated,
+                source: None,
+                name: None,
+                original: None,
+                range: false,
+            })
+        }
+    };
+    let source = sources.get(source_idx).ok_or(Error::SourceOutOfRange(source_idx))?;
+    let name = match name_idx {
+        Some(idx) => Some(names.get(idx).ok_or(Error::NameOutOfRange(idx))?.clone()),
+        None => None,
+    };
+    Ok(Mapping {
+        generated,
+        source: Some(source.clone()),
+        name,
+        original: Some((line + 1, column)),
+        range,
+    })
+}
+
+// text of `line` in `content`, without its terminator
+pub(crate) fn content_line(content: &str, line_terminators: LineTerminators, line: usize) -> Option<&str> {
+    let lines = line_terminators.split_lines(content);
+    let line = lines.get(line.checked_sub(1)?)?;
+    Some(line_terminators.trim_terminator(line))
+}
+
+// converts the columns of `mapping`, found on the generated line `generated_line`, from
+// `from` to `to`; the original column only when `original_line` knows its line
+pub(crate) fn convert_mapping<'a, F>(
+    mut mapping: Mapping,
+    generated_line: &str,
+    from: ColumnUnit,
+    to: ColumnUnit,
+    original_line: F,
+) -> Mapping
+where
+    F: Fn(&str, usize) -> Option<&'a str>,
+{
+    if mapping.generated.0 > 0 {
+        mapping.generated.1 = convert_column(generated_line, mapping.generated.1, from, to);
+        if let (Some(source), Some((line, column))) = (&mapping.source, mapping.original) {
+            if let Some(text) = original_line(source, line) {
+                mapping.original = Some((line, convert_column(text, column, from, to)));
+            }
+        }
+    }
+    mapping
+}
+
+// looks a generated position up in `mappings` sorted by generated position; a position
+// strictly inside a range mapping maps exactly, whatever the bias
+pub(crate) fn original_position_in(mappings: &[Mapping], line: usize, column: usize, bias: Bias) -> Mapping {
+    let key = |mapping: &Mapping| mapping.generated;
+    if let Some(idx) = utils::binary_search(mappings, &(line, column), key, Bias::GreatestLowerBound) {
+        let mapping = &mappings[idx];
+        if mapping.range && mapping.generated.0 == line && mapping.generated.1 < column {
+            return mapping.at_column(column);
+        }
+    }
+    if let Some(idx) = utils::binary_search(mappings, &(line, column), key, bias) {
+        let mapping = &mappings[idx];
+        if mapping.generated.0 == line {
+            return mapping.clone();
+        }
+    }
+    Mapping {
+        generated: (0, 0),
+        source: None,
+        name: None,
+        original: None,
+        range: false,
+    }
+}
@@ -1,25 +1,31 @@
+use error::Error;
 use linked_hash_map::LinkedHashMap;
 use mapping::Mapping;
 use mapping_list::MappingList;
+use scopes::{self, GeneratedRange, OriginalScope};
 use source_map::SrcMap;
+use source_map_consumer::{Bias, SourceMapConsumer};
+use serde_json;
 use std::collections::{HashMap, HashSet};
-use std::rc::Rc;
-use std::str;
+use std::io::{self, Write};
 use utils;
 use vlq;
+use Ptr;
 use StringPtr;
-use source_map_mappings::parse_mappings;
-use serde_json;
 
 #[derive(Debug)]
 pub struct SourceMapGenerator {
-    file: Option<Rc<String>>,
-    source_root: Option<Rc<String>>,
+    file: Option<Ptr<String>>,
+    source_root: Option<Ptr<String>>,
+    debug_id: Option<Ptr<String>>,
     skip_validation: bool,
-    sources: LinkedHashMap<Rc<String>, usize>,
-    names: LinkedHashMap<Rc<String>, usize>,
+    sources: LinkedHashMap<Ptr<String>, usize>,
+    names: LinkedHashMap<Ptr<String>, usize>,
     pub mappings: MappingList,
-    pub sources_contents: HashMap<Rc<String>, Rc<String>>,
+    pub sources_contents: HashMap<Ptr<String>, Ptr<String>>,
+    pub ignored_sources: HashSet<Ptr<String>>,
+    original_scopes: LinkedHashMap<Ptr<String>, Vec<OriginalScope>>,
+    generated_ranges: Vec<GeneratedRange>,
 }
 
 impl SourceMapGenerator {
@@ -33,19 +39,28 @@ impl SourceMapGenerator {
         SourceMapGenerator {
             file,
             source_root,
+            debug_id: None,
             skip_validation,
             sources: LinkedHashMap::new(),
             names: LinkedHashMap::new(),
             mappings: MappingList::new(),
             sources_contents: HashMap::new(),
+            ignored_sources: HashSet::new(),
+            original_scopes: LinkedHashMap::new(),
+            generated_ranges: Vec::new(),
         }
     }
 
-    pub fn add_mapping(&mut self, map: Mapping) {
+    pub fn add_mapping(&mut self, map: Mapping) -> Result<(), Error> {
         if !self.skip_validation {
-            SourceMapGenerator::validate_mapping(&map).unwrap();
+            SourceMapGenerator::validate_mapping(&map)?;
         }
+        self.push_mapping(map);
+        Ok(())
+    }
 
+    // for mappings built by this crate, which are valid by construction
+    pub(crate) fn push_mapping(&mut self, map: Mapping) {
         if let Some(source) = map.source.clone() {
             let len = self.sources.len();
             self.sources.entry(source).or_insert(len);
@@ -68,7 +83,7 @@ impl SourceMapGenerator {
         let source_content = source_content.map(|sp| sp.to_ptr());
 
         let source = if let Some(root) = self.source_root.clone() {
-            Rc::new(utils::relative(&root, &source_file))
+            Ptr::new(utils::relative(&root, &source_file))
         } else {
             source_file
         };
@@ -80,63 +95,360 @@ impl SourceMapGenerator {
         }
     }
 
-    pub fn to_source_map(&mut self) -> SrcMap {
+    pub fn set_original_scopes(&mut self, source: StringPtr, scopes: Vec<OriginalScope>) {
+        let source = source.to_ptr();
+        let source = if let Some(root) = self.source_root.clone() {
+            Ptr::new(utils::relative(&root, &source))
+        } else {
+            source
+        };
+        self.original_scopes.insert(source, scopes);
+    }
+
+    // `range` is a top-level range, nested ones are its `children`
+    pub fn add_generated_range(&mut self, range: GeneratedRange) {
+        let idx = self
+            .generated_ranges
+            .iter()
+            .position(|other| other.start > range.start)
+            .unwrap_or(self.generated_ranges.len());
+        self.generated_ranges.insert(idx, range);
+    }
+
+    pub fn set_debug_id(&mut self, debug_id: Option<StringPtr>) {
+        self.debug_id = debug_id.map(|sp| sp.to_ptr());
+    }
+
+    pub fn set_source_ignored(&mut self, source_file: StringPtr, ignored: bool) {
+        let source_file = source_file.to_ptr();
+        let source = if let Some(root) = self.source_root.clone() {
+            Ptr::new(utils::relative(&root, &source_file))
+        } else {
+            source_file
+        };
+
+        if ignored {
+            self.ignored_sources.insert(source);
+        } else {
+            self.ignored_sources.remove(&source);
+        }
+    }
+
+    // marks every source added so far for which `predicate` returns true as ignored
+    pub fn ignore_sources<F>(&mut self, predicate: F)
+    where
+        F: Fn(&str) -> bool,
+    {
+        let sources: Vec<Ptr<String>> = self
+            .sources
+            .keys()
+            .filter(|source| predicate(source))
+            .cloned()
+            .collect();
+        for source in sources {
+            self.set_source_ignored(StringPtr::Ptr(source), true);
+        }
+    }
+
+    fn is_source_ignored(&self, source: &Ptr<String>) -> bool {
+        match self.source_root {
+            Some(ref root) => self.ignored_sources.contains(&utils::relative(root, source)),
+            None => self.ignored_sources.contains(source),
+        }
+    }
+
+    // originate from `SourceMapGenerator.applySourceMap`, `source_map_path` is the dirname
+    // of the applied map and `source_file` defaults to its `file` property
+    pub fn apply_source_map(
+        &mut self,
+        consumer: &SourceMapConsumer,
+        source_file: Option<StringPtr>,
+        source_map_path: Option<StringPtr>,
+    ) -> Result<(), Error> {
+        let source_file = source_file
+            .map(|sp| sp.to_ptr())
+            .or_else(|| consumer.file().cloned())
+            .ok_or(Error::MissingSourceFile)?;
+        let source_map_path = source_map_path.map(|sp| sp.to_ptr());
+        let source_root = self.source_root.clone();
+        let join = |source: &str| -> Ptr<String> {
+            match source_map_path {
+                Some(ref path) => Ptr::new(utils::join(path, source)),
+                None => Ptr::new(String::from(source)),
+            }
+        };
+
+        // make `source_file` relative if an absolute url is passed
+        let source_file = match source_root {
+            Some(ref root) => Ptr::new(utils::relative(root, &source_file)),
+            None => source_file,
+        };
+
+        let mut sources: LinkedHashMap<Ptr<String>, usize> = LinkedHashMap::new();
+        let mut names: LinkedHashMap<Ptr<String>, usize> = LinkedHashMap::new();
+        self.mappings.unsorted_for_each_mut(|mapping| {
+            if mapping.source.as_ref() == Some(&source_file) {
+                if let Some((line, column)) = mapping.original {
+                    let original = consumer.original_position_for(line, column, Bias::GreatestLowerBound);
+                    if let Some(source) = original.source {
+                        let source = join(&source);
+                        mapping.source = match source_root {
+                            Some(ref root) => Some(Ptr::new(utils::relative(root, &source))),
+                            None => Some(source),
+                        };
+                        mapping.original = original.original;
+                        mapping.range = mapping.range && original.range;
+                        if original.name.is_some() {
+                            mapping.name = original.name;
+                        }
+                    }
+                }
+            }
+
+            if let Some(ref source) = mapping.source {
+                let len = sources.len();
+                sources.entry(source.clone()).or_insert(len);
+            }
+            if let Some(ref name) = mapping.name {
+                let len = names.len();
+                names.entry(name.clone()).or_insert(len);
+            }
+        });
+        self.sources = sources;
+        self.names = names;
+
+        // copy sources contents and ignored sources of the applied map
+        for source in consumer.sources() {
+            if let Some(content) = consumer.source_content_for(source) {
+                self.set_source_content(StringPtr::Ptr(join(source)), Some(StringPtr::Ptr(content)));
+            }
+            if consumer.is_ignored(source) {
+                self.set_source_ignored(StringPtr::Ptr(join(source)), true);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_source_map(&mut self) -> Result<SrcMap, Error> {
+        self.register_scopes();
         let version = 3;
         let sources: Vec<String> = self.sources.keys().map(|sp| (**sp).clone()).collect();
         let names: Vec<String> = self.names.keys().map(|sp| (**sp).clone()).collect();
-        let mappings = self.serialize_mappings();
+        let mappings = self.serialize_mappings()?;
+        let range_mappings = self.serialize_range_mappings();
+        let (original_scopes, generated_ranges) = self.serialize_scopes()?;
         let file = self.file.clone().map(|sp| (*sp).clone());
         let source_root = self.source_root.clone().map(|sp| (*sp).clone());
-        let mut sources_content: Vec<String> = Vec::new();
+        let mut sources_content: Vec<Option<String>> = Vec::new();
 
-        for src in self.sources.keys() {
-            if let Some(content) = self.sources_contents.get(src) {
-                sources_content.push((**content).clone());
+        // contents stay aligned with sources, missing ones are serialized as `null`
+        if !self.sources_contents.is_empty() {
+            for src in self.sources.keys() {
+                sources_content.push(self.source_content(src).map(|content| (**content).clone()));
             }
         }
-        return SrcMap {
+        let ignore_list = self.ignore_list();
+        Ok(SrcMap {
             version,
             sources,
             names,
             mappings,
+            range_mappings,
+            original_scopes,
+            generated_ranges,
             file,
             source_root,
             sources_content,
+            ignore_list,
+            x_google_ignore_list: Vec::new(),
+            debug_id: self.debug_id.clone().map(|sp| (*sp).clone()),
+        })
+    }
+
+    fn validate_mapping(map: &Mapping) -> Result<(), Error> {
+        let valid = if let Some((original_line, _)) = map.original {
+            map.source.is_some() && original_line > 0 && map.generated.0 > 0
+        } else {
+            map.source.is_none() && map.name.is_none() && !map.range && map.generated.0 > 0
         };
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidMapping(map.clone()))
+        }
+    }
+
+    // writes the same JSON as `to_source_map` followed by `SrcMap::to_json`, without
+    // building the mappings string or copying sources; an invalid mapping is only
+    // detected once the output before it has been written
+    pub fn write_source_map<W: Write>(&mut self, mut out: W) -> Result<(), Error> {
+        self.register_scopes();
+        write!(out, "{{\"version\":3")?;
+        if let Some(ref file) = self.file {
+            write!(out, ",\"file\":")?;
+            serde_json::to_writer(&mut out, &**file)?;
+        }
+        if let Some(ref source_root) = self.source_root {
+            write!(out, ",\"sourceRoot\":")?;
+            serde_json::to_writer(&mut out, &**source_root)?;
+        }
+        write!(out, ",\"sources\":")?;
+        write_strings(&mut out, self.sources.keys())?;
+        if !self.sources_contents.is_empty() {
+            write!(out, ",\"sourcesContent\":[")?;
+            for (i, src) in self.sources.keys().enumerate() {
+                if i > 0 {
+                    write!(out, ",")?;
+                }
+                serde_json::to_writer(&mut out, &self.source_content(src).map(|content| &**content))?;
+            }
+            write!(out, "]")?;
+        }
+        write!(out, ",\"names\":")?;
+        write_strings(&mut out, self.names.keys())?;
+        write!(out, ",\"mappings\":\"")?;
+        self.write_mappings(&mut out)?;
+        write!(out, "\"")?;
+        if let Some(range_mappings) = self.serialize_range_mappings() {
+            write!(out, ",\"rangeMappings\":\"{}\"", range_mappings)?;
+        }
+        let (original_scopes, generated_ranges) = self.serialize_scopes()?;
+        if !original_scopes.is_empty() {
+            write!(out, ",\"originalScopes\":")?;
+            serde_json::to_writer(&mut out, &original_scopes)?;
+        }
+        if let Some(generated_ranges) = generated_ranges {
+            write!(out, ",\"generatedRanges\":\"{}\"", generated_ranges)?;
+        }
+        let ignore_list = self.ignore_list();
+        if !ignore_list.is_empty() {
+            write!(out, ",\"ignoreList\":")?;
+            serde_json::to_writer(&mut out, &ignore_list)?;
+        }
+        if let Some(ref debug_id) = self.debug_id {
+            write!(out, ",\"debugId\":")?;
+            serde_json::to_writer(&mut out, &**debug_id)?;
+        }
+        write!(out, "}}")?;
+        Ok(())
     }
 
-    fn validate_mapping(map: &Mapping) -> Result<(), &'static str> {
-        if let Some((original_line, _)) = map.original.clone() {
-            if map.source.is_some() && original_line > 0 && map.generated.0 > 0 {
-                Ok(())
-            } else {
-                Err("Invalid mapping")
+    // sources with scopes and names used by scopes, after the ones of the mappings
+    fn register_scopes(&mut self) {
+        let mut names = Vec::new();
+        for scope in self.original_scopes.values().flat_map(|scopes| scopes.iter()) {
+            scope.collect_names(&mut names);
+        }
+        for range in &self.generated_ranges {
+            range.collect_names(&mut names);
+        }
+        let names: Vec<Ptr<String>> = names.into_iter().map(|name| Ptr::new(String::from(name))).collect();
+        for name in names {
+            let len = self.names.len();
+            self.names.entry(name).or_insert(len);
+        }
+        let registered: HashSet<Ptr<String>> = self.sources.keys().map(|source| self.relative_source(source)).collect();
+        for source in self.original_scopes.keys() {
+            if !registered.contains(source) {
+                let len = self.sources.len();
+                self.sources.insert(source.clone(), len);
             }
+        }
+    }
+
+    fn serialize_scopes(&self) -> Result<(Vec<String>, Option<String>), Error> {
+        let name_index = |name: &str| self.names[&String::from(name)];
+        let original_scopes = if self.original_scopes.is_empty() {
+            Vec::new()
         } else {
-            if map.source.is_none() && map.name.is_none() && map.generated.0 > 0 {
-                Ok(())
-            } else {
-                Err("Invalid mapping")
+            self.sources
+                .keys()
+                .map(|source| {
+                    self.original_scopes
+                        .get(&self.relative_source(source))
+                        .map_or(String::new(), |scopes| scopes::encode_original_scopes(scopes, &name_index))
+                })
+                .collect()
+        };
+        let generated_ranges = if self.generated_ranges.is_empty() {
+            None
+        } else {
+            let source_index = |source: &str| self.sources.get(&String::from(source)).cloned();
+            Some(scopes::encode_generated_ranges(&self.generated_ranges, &source_index, &name_index)?)
+        };
+        Ok((original_scopes, generated_ranges))
+    }
+
+    // `source` as the side tables keyed by source are, relative to the source root
+    fn relative_source(&self, source: &Ptr<String>) -> Ptr<String> {
+        match self.source_root {
+            Some(ref root) => Ptr::new(utils::relative(root, source)),
+            None => source.clone(),
+        }
+    }
+
+    fn source_content(&self, source: &Ptr<String>) -> Option<&Ptr<String>> {
+        match self.source_root {
+            Some(ref root) => self.sources_contents.get(&utils::relative(root, source)),
+            None => self.sources_contents.get(source),
+        }
+    }
+
+    fn ignore_list(&self) -> Vec<usize> {
+        self.sources
+            .iter()
+            .filter(|&(source, _)| self.is_source_ignored(source))
+            .map(|(_, idx)| *idx)
+            .collect()
+    }
+
+    fn serialize_mappings(&mut self) -> Result<String, Error> {
+        let mut result = Vec::new();
+        self.write_mappings(&mut result)?;
+        // VLQ segments and separators are all ASCII
+        Ok(String::from_utf8(result).unwrap())
+    }
+
+    // `None` when there are no range mappings, valid once the mappings have been written
+    fn serialize_range_mappings(&self) -> Option<String> {
+        let mut lines: Vec<Vec<usize>> = Vec::new();
+        let mut line = 0;
+        let mut idx = 0;
+        for mapping in &self.mappings.list {
+            if mapping.generated.0 != line {
+                line = mapping.generated.0;
+                idx = 0;
+            }
+            if mapping.range {
+                // lines are stored 0-based in SourceMap spec version 3
+                lines.resize(lines.len().max(line), Vec::new());
+                lines[line - 1].push(idx);
             }
+            idx += 1;
+        }
+        if lines.is_empty() {
+            None
+        } else {
+            Some(utils::serialize_range_mappings(&lines))
         }
     }
 
-    fn serialize_mappings(&mut self) -> String {
+    fn write_mappings<W: Write>(&mut self, out: &mut W) -> Result<(), Error> {
         // (line, column)
         let mut previous_generated: (usize, usize) = (1, 0);
         let mut previous_original: (usize, usize) = (0, 0);
         let mut previous_name: usize = 0;
         let mut previous_source: usize = 0;
-        let mut result = String::new();
         let mut buf = Vec::<u8>::new();
 
         self.mappings.sort();
         for (i, mapping) in self.mappings.list.iter().enumerate() {
-            if mapping.generated.0 != previous_generated.0 {
+            let invalid = || Error::InvalidMapping(mapping.clone());
+            if mapping.generated.0 < previous_generated.0 {
+                return Err(invalid());
+            } else if mapping.generated.0 != previous_generated.0 {
                 previous_generated.1 = 0;
-                for _ in 0..(mapping.generated.0 - previous_generated.0) {
-                    buf.push(b';');
-                }
+                buf.resize(buf.len() + mapping.generated.0 - previous_generated.0, b';');
                 previous_generated.0 = mapping.generated.0;
             } else if i > 0 {
                 //     if (
@@ -157,11 +469,14 @@ impl SourceMapGenerator {
             ).unwrap();
             previous_generated.1 = mapping.generated.1;
             if let Some(ref source) = mapping.source {
-                let source_idx = self.sources.get(source).unwrap();
+                let source_idx = self.sources.get(source).ok_or_else(invalid)?;
                 vlq::encode(*source_idx as i64 - previous_source as i64, &mut buf).unwrap();
                 previous_source = *source_idx;
 
-                let mapping_original = mapping.original.unwrap();
+                let mapping_original = match mapping.original {
+                    Some(original) if original.0 > 0 => original,
+                    _ => return Err(invalid()),
+                };
                 // lines are stored 0-based in SourceMap spec version 3
                 vlq::encode(
                     mapping_original.0 as i64 - 1 - previous_original.0 as i64,
@@ -176,15 +491,15 @@ impl SourceMapGenerator {
                 previous_original.1 = mapping_original.1;
 
                 if let Some(ref name) = mapping.name {
-                    let name_idx = self.names.get(name).unwrap();
+                    let name_idx = self.names.get(name).ok_or_else(invalid)?;
                     vlq::encode(*name_idx as i64 - previous_name as i64, &mut buf).unwrap();
                     previous_name = *name_idx;
                 }
             }
-            result += str::from_utf8(&buf).unwrap();
+            out.write_all(&buf)?;
             buf.clear();
         }
-        result
+        Ok(())
     }
 
     // originate from `SourceMapConsumer.OriginalPositionFor`
@@ -198,11 +513,27 @@ impl SourceMapGenerator {
                 return mapping.clone();
             }
         }
-        return Mapping {
+        Mapping {
             generated: (0, 0),
             source: None,
             name: None,
-            original: None
+            original: None,
+            range: false,
+        }
+    }
+}
+
+fn write_strings<'a, W, I>(out: &mut W, strings: I) -> io::Result<()>
+where
+    W: Write,
+    I: Iterator<Item = &'a Ptr<String>>,
+{
+    write!(out, "[")?;
+    for (i, s) in strings.enumerate() {
+        if i > 0 {
+            write!(out, ",")?;
         }
+        serde_json::to_writer(&mut *out, &**s)?;
     }
+    write!(out, "]")
 }
@@ -0,0 +1,94 @@
+use base64;
+use error::Error;
+use regex::Regex;
+use source_map::{SrcMap, StringWithSrcMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use utils;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentStyle {
+    // `//# sourceMappingURL=...`
+    Js,
+    // `/*# sourceMappingURL=... */`
+    Css,
+}
+
+pub fn source_mapping_url_comment(url: &str, style: CommentStyle) -> String {
+    match style {
+        CommentStyle::Js => format!("//# sourceMappingURL={}", url),
+        CommentStyle::Css => format!("/*# sourceMappingURL={} */", url),
+    }
+}
+
+pub fn to_data_url(map: &SrcMap) -> Result<String, Error> {
+    Ok(String::from("data:application/json;charset=utf-8;base64,") + &base64::encode(map.to_json()?))
+}
+
+// accepts base64 and percent-encoded `data:application/json` URLs
+pub fn from_data_url(url: &str) -> Result<SrcMap, Error> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"(?s)^data:application/json(?:;charset=[\w-]+)?(;base64)?,(.*)$").unwrap();
+    }
+    let caps = RE.captures(url).ok_or(Error::InvalidDataUrl)?;
+    let json = if caps.get(1).is_some() {
+        let json = base64::decode(&caps[2]).map_err(|_| Error::InvalidDataUrl)?;
+        String::from_utf8(json).map_err(|_| Error::InvalidDataUrl)?
+    } else {
+        utils::percent_decode(&caps[2]).ok_or(Error::InvalidDataUrl)?
+    };
+    SrcMap::from_json(&json)
+}
+
+// returns the URL of the last `sourceMappingURL` comment in generated code
+pub fn find_source_mapping_url(code: &str) -> Option<String> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(
+            r#"(?m)^[ \t]*(?://[#@][ \t]*sourceMappingURL=([^\s'"]+)|/\*[#@][ \t]*sourceMappingURL=([^\s*'"]+)[ \t]*\*/)[ \t]*\r?$"#
+        ).unwrap();
+    }
+    RE.captures_iter(code)
+        .last()
+        .and_then(|caps| caps.get(1).or_else(|| caps.get(2)))
+        .map(|m| String::from(m.as_str()))
+}
+
+// resolves an external map URL against the path of the generated file it was found in
+pub fn resolve_source_mapping_url(url: &str, generated_path: &Path) -> Result<PathBuf, Error> {
+    if let Some(path) = url.strip_prefix("file://") {
+        let path = utils::percent_decode(path).ok_or_else(|| Error::UnsupportedUrl(String::from(url)))?;
+        return Ok(PathBuf::from(path));
+    }
+    if utils::is_absolute(url) && !url.starts_with('/') {
+        return Err(Error::UnsupportedUrl(String::from(url)));
+    }
+    let dir = generated_path.parent().unwrap_or_else(|| Path::new(""));
+    Ok(dir.join(url))
+}
+
+// loads the map referenced by the generated code at `generated_path`, `None` when
+// the code has no `sourceMappingURL` comment
+pub fn load_source_map(code: &str, generated_path: &Path) -> Result<Option<SrcMap>, Error> {
+    let url = match find_source_mapping_url(code) {
+        Some(url) => url,
+        None => return Ok(None),
+    };
+    if url.starts_with("data:") {
+        return from_data_url(&url).map(Some);
+    }
+    let json = fs::read_to_string(resolve_source_mapping_url(&url, generated_path)?)?;
+    SrcMap::from_json(&json).map(Some)
+}
+
+impl StringWithSrcMap {
+    pub fn append_source_mapping_url(&mut self, url: &str, style: CommentStyle) {
+        utils::push_line(&mut self.source, &source_mapping_url_comment(url, style));
+    }
+
+    pub fn append_inline_source_map(&mut self, style: CommentStyle) -> Result<(), Error> {
+        let url = to_data_url(&self.map)?;
+        self.append_source_mapping_url(&url, style);
+        Ok(())
+    }
+}
@@ -1,55 +1,97 @@
+use column_unit::ColumnUnit;
+use debug_id;
+use line_terminators::LineTerminators;
+use error::Error;
 use mapping::Mapping;
-use source_map::StringWithSrcMap;
+use regex::Regex;
+use source_map::{SrcMap, StringWithSrcMap};
+use source_map_consumer::{MappingOrder, SourceMapConsumer};
 use source_map_generator::SourceMapGenerator;
-use source_map_consumer::SourceMapConsumer;
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::mem;
+use utils;
+use IntoOption;
 use Node;
+use Ptr;
 use StringPtr;
-use serde_json;
 
-#[derive(Clone, Debug)]
+// `source_node![(line, column, source[, name]) => chunk, ...]` builds a mapped node and
+// `source_node![chunk, ...]` an unmapped one, chunks being anything convertible to `Node`
+#[macro_export]
+macro_rules! source_node {
+    (($line:expr, $column:expr, $source:expr, $name:expr) => $($chunk:expr),* $(,)?) => {
+        $crate::SourceNode::at($line, $column, $source).named($name)$(.child($chunk))*
+    };
+    (($line:expr, $column:expr, $source:expr) => $($chunk:expr),* $(,)?) => {
+        $crate::SourceNode::at($line, $column, $source)$(.child($chunk))*
+    };
+    ($($chunk:expr),* $(,)?) => {
+        $crate::SourceNode::default()$(.child($chunk))*
+    };
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct SourceNode {
     pub children: Vec<Node>,
-    pub source_contents: HashMap<Rc<String>, Rc<String>>,
+    pub source_contents: HashMap<Ptr<String>, Ptr<String>>,
+    pub ignored_sources: HashSet<Ptr<String>>,
     // (line, column)
     pub position: Option<(usize, usize)>,
-    pub source: Option<Rc<String>>,
-    pub name: Option<Rc<String>>,
+    pub source: Option<Ptr<String>>,
+    pub name: Option<Ptr<String>>,
 }
 
 impl SourceNode {
     pub fn new(
-        position: Option<(usize, usize)>,
-        source: Option<StringPtr>,
-        name: Option<StringPtr>,
-        chunks: Option<Node>,
+        position: impl Into<Option<(usize, usize)>>,
+        source: impl IntoOption<StringPtr>,
+        name: impl IntoOption<StringPtr>,
+        chunks: impl IntoOption<Node>,
     ) -> SourceNode {
-        let source = source.map(|sp| sp.to_ptr());
-        let name = name.map(|sp| sp.to_ptr());
+        let source = source.into_option().map(|sp| sp.to_ptr());
+        let name = name.into_option().map(|sp| sp.to_ptr());
         let mut sn = SourceNode {
-            position,
+            position: position.into(),
             source,
             name,
             children: Vec::new(),
             source_contents: HashMap::new(),
+            ignored_sources: HashSet::new(),
         };
-        if let Some(chunks) = chunks {
+        if let Some(chunks) = chunks.into_option() {
             sn.add(chunks);
         }
         sn
     }
 
-    pub fn add(&mut self, chunk: Node) {
-        match chunk {
-            Node::NNodeVec(mut nv) => {
-                self.children.append(&mut nv);
+    // a node at `(line, column)` of `source` without any chunk yet
+    pub fn at(line: usize, column: usize, source: impl Into<StringPtr>) -> SourceNode {
+        SourceNode::new(Some((line, column)), Some(source.into()), None, None)
+    }
+
+    pub fn named(mut self, name: impl Into<StringPtr>) -> SourceNode {
+        self.name = Some(name.into().to_ptr());
+        self
+    }
+
+    pub fn child(mut self, chunk: impl Into<Node>) -> SourceNode {
+        self.add(chunk);
+        self
+    }
+
+    pub fn add(&mut self, chunk: impl Into<Node>) {
+        match chunk.into() {
+            Node::NNodeVec(nv) => {
+                for chunk in nv {
+                    self.add(chunk);
+                }
             }
             Node::NSourceNode(sn) => {
                 self.children.push(Node::NSourceNode(sn));
             }
             Node::NString(s) => {
-                self.children.push(Node::NRcString(Rc::new(s)));
+                self.children.push(Node::NRcString(Ptr::new(s)));
             }
             Node::NRcString(sp) => {
                 self.children.push(Node::NRcString(sp));
@@ -57,76 +99,247 @@ impl SourceNode {
         }
     }
 
-    pub fn set_source_content(&mut self, source: StringPtr, source_content: StringPtr) {
-        let source = source.to_ptr();
-        let source_content = source_content.to_ptr();
+    pub fn prepend(&mut self, chunk: impl Into<Node>) {
+        let children = mem::take(&mut self.children);
+        self.add(chunk);
+        self.children.extend(children);
+    }
+
+    // originate from `SourceNode.prototype.join`, except that the separators are
+    // always unmapped
+    pub fn join(&mut self, separator: impl Into<StringPtr>) {
+        let separator = separator.into().to_ptr();
+        let children = mem::take(&mut self.children);
+        for (i, child) in children.into_iter().enumerate() {
+            if i > 0 {
+                let chunk = Node::NRcString(separator.clone());
+                self.children.push(Node::NSourceNode(SourceNode::new(None, None, None, Some(chunk))));
+            }
+            self.children.push(child);
+        }
+    }
+
+    // originate from `SourceNode.prototype.replaceRight`, replaces the first match of
+    // `pattern` in the last string chunk, looking into the last child node if needed
+    pub fn replace_right(&mut self, pattern: &Regex, replacement: &str) {
+        match self.children.last_mut() {
+            Some(Node::NSourceNode(sn)) => sn.replace_right(pattern, replacement),
+            Some(Node::NRcString(chunk)) => {
+                let replaced = pattern.replace(chunk, replacement).into_owned();
+                *chunk = Ptr::new(replaced);
+            }
+            Some(Node::NString(chunk)) => {
+                let replaced = pattern.replace(chunk, replacement).into_owned();
+                *chunk = replaced;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn set_source_content(
+        &mut self,
+        source: impl Into<StringPtr>,
+        source_content: impl Into<StringPtr>,
+    ) {
+        let source = source.into().to_ptr();
+        let source_content = source_content.into().to_ptr();
         self.source_contents.insert(source, source_content);
     }
 
+    pub fn set_source_ignored(&mut self, source: impl Into<StringPtr>, ignored: bool) {
+        let source = source.into().to_ptr();
+        if ignored {
+            self.ignored_sources.insert(source);
+        } else {
+            self.ignored_sources.remove(&source);
+        }
+    }
+
     pub fn to_string_with_source_map(
         &self,
         file: Option<StringPtr>,
         source_root: Option<StringPtr>,
-    ) -> StringWithSrcMap {
+    ) -> Result<StringWithSrcMap, Error> {
+        self.to_string_with_source_map_in(
+            file,
+            source_root,
+            ColumnUnit::default(),
+            LineTerminators::default(),
+        )
+    }
+
+    // generated columns are counted in `column_unit` and lines end at `line_terminators`
+    pub fn to_string_with_source_map_in(
+        &self,
+        file: Option<StringPtr>,
+        source_root: Option<StringPtr>,
+        column_unit: ColumnUnit,
+        line_terminators: LineTerminators,
+    ) -> Result<StringWithSrcMap, Error> {
         let file = file.map(|sp| sp.to_ptr());
         let source_root = source_root.map(|sp| sp.to_ptr());
         let skip_validation = true;
-        let mut context = ToSourceMapContext::new(file, source_root, skip_validation);
-        self.walk(&mut context);
+        let mut context = ToSourceMapContext::new(
+            file,
+            source_root,
+            skip_validation,
+            column_unit,
+            line_terminators,
+        );
+        self.visit(&mut context);
 
-        StringWithSrcMap {
+        Ok(StringWithSrcMap {
+            map: context.map.to_source_map()?,
             source: context.generated_code,
-            map: context.map.to_source_map(),
-        }
+        })
+    }
+
+    // like `to_string_with_source_map`, additionally tagging the code and the map with
+    // a deterministic debug ID through a trailing `//# debugId=` comment
+    pub fn to_string_with_debug_id(
+        &self,
+        file: Option<StringPtr>,
+        source_root: Option<StringPtr>,
+    ) -> Result<StringWithSrcMap, Error> {
+        let mut result = self.to_string_with_source_map(file, source_root)?;
+        let id = debug_id::generate_debug_id(&result.source, &result.map)?;
+        utils::push_line(&mut result.source, &debug_id::debug_id_comment(&id));
+        result.map.debug_id = Some(id);
+        Ok(result)
     }
 
     pub fn to_source_map_generator(&self, file: Option<StringPtr>, source_root: Option<StringPtr>)
         -> SourceMapGenerator {
+        self.to_source_map_generator_in(
+            file,
+            source_root,
+            ColumnUnit::default(),
+            LineTerminators::default(),
+        )
+    }
+
+    pub fn to_source_map_generator_in(
+        &self,
+        file: Option<StringPtr>,
+        source_root: Option<StringPtr>,
+        column_unit: ColumnUnit,
+        line_terminators: LineTerminators,
+    ) -> SourceMapGenerator {
         let file = file.map(|sp| sp.to_ptr());
         let source_root = source_root.map(|sp| sp.to_ptr());
         let skip_validation = true;
-        let mut context = ToSourceMapContext::new(file, source_root, skip_validation);
-        self.walk(&mut context);
+        let mut context = ToSourceMapContext::new(
+            file,
+            source_root,
+            skip_validation,
+            column_unit,
+            line_terminators,
+        );
+        self.visit(&mut context);
 
         context.map
     }
 
-    fn walk<T: WalkFunction>(&self, context: &mut T) {
+    // depth first, a node's source contents and ignored sources come after its children
+    pub fn visit<V: SourceNodeVisitor + ?Sized>(&self, visitor: &mut V) {
         for child in &self.children {
             match child {
                 Node::NSourceNode(sn) => {
-                    sn.walk(context);
+                    sn.visit(visitor);
                 }
                 Node::NRcString(chunk) => {
-                    context.process_chunk(&chunk, &self.source, &self.position, &self.name);
+                    visitor.visit_chunk(chunk, self.source.as_ref(), self.position, self.name.as_ref());
+                }
+                Node::NString(chunk) => {
+                    visitor.visit_chunk(chunk, self.source.as_ref(), self.position, self.name.as_ref());
                 }
-                _ => {}
+                Node::NNodeVec(_) => {}
             }
         }
         for (source, source_content) in &self.source_contents {
-            context.process_source_content(source, source_content);
+            visitor.visit_source_content(source, source_content);
+        }
+        for source in &self.ignored_sources {
+            visitor.visit_ignored_source(source);
+        }
+    }
+
+    // originate from `SourceNode.prototype.walk`
+    pub fn walk<F>(&self, f: F)
+    where
+        F: FnMut(&str, Option<&Ptr<String>>, Option<(usize, usize)>, Option<&Ptr<String>>),
+    {
+        struct Walk<F>(F);
+        impl<F> SourceNodeVisitor for Walk<F>
+        where
+            F: FnMut(&str, Option<&Ptr<String>>, Option<(usize, usize)>, Option<&Ptr<String>>),
+        {
+            fn visit_chunk(
+                &mut self,
+                chunk: &str,
+                source: Option<&Ptr<String>>,
+                position: Option<(usize, usize)>,
+                name: Option<&Ptr<String>>,
+            ) {
+                (self.0)(chunk, source, position, name);
+            }
+        }
+        self.visit(&mut Walk(f));
+    }
+
+    // originate from `SourceNode.prototype.walkSourceContents`
+    pub fn walk_source_contents<F: FnMut(&Ptr<String>, &Ptr<String>)>(&self, f: F) {
+        struct WalkSourceContents<F>(F);
+        impl<F: FnMut(&Ptr<String>, &Ptr<String>)> SourceNodeVisitor for WalkSourceContents<F> {
+            fn visit_chunk(&mut self, _: &str, _: Option<&Ptr<String>>, _: Option<(usize, usize)>, _: Option<&Ptr<String>>) {}
+
+            fn visit_source_content(&mut self, source: &Ptr<String>, source_content: &Ptr<String>) {
+                (self.0)(source, source_content);
+            }
         }
+        self.visit(&mut WalkSourceContents(f));
     }
 
-    pub fn from_string_with_source_map(code: &str, generator: SourceMapGenerator) -> SourceNode {
+    // originate from `SourceNode.fromStringWithSourceMap`, sources are the ones of the
+    // consumer, which already have `sourceRoot` applied, joined to `relative_path`;
+    // `code` is split with the consumer's line terminators and column unit; a node has a
+    // single position, so range mappings are cut to their first column
+    pub fn from_string_with_source_map(
+        code: &str,
+        consumer: &SourceMapConsumer,
+        relative_path: Option<&str>,
+    ) -> SourceNode {
         let mut node = SourceNode::new(None, None, None, None);
+        let column_unit = consumer.column_unit();
+        let resolved: HashMap<Ptr<String>, Ptr<String>> = consumer
+            .sources()
+            .iter()
+            .map(|source| {
+                let resolved = match relative_path {
+                    Some(path) => Ptr::new(utils::join(path, source)),
+                    None => source.clone(),
+                };
+                (source.clone(), resolved)
+            })
+            .collect();
+        let mut mappings = Vec::new();
+        consumer.each_mapping(MappingOrder::Generated, |mapping| {
+            let mut mapping = mapping.clone();
+            mapping.source = mapping.source.map(|source| resolved[&source].clone());
+            mappings.push(mapping);
+        });
 
-        let mut lines = code.split('\n').peekable();
+        let mut lines = consumer.line_terminators().split_lines(code).into_iter();
         let mut last_generated_position: (usize, usize) = (1, 0);
         let mut last_mapping: Option<Mapping> = None;
         let mut shift_lines = || {
-            let next = lines.next();
-            next.map_or((String::new(), false), |line| {
-                if lines.peek().is_some() {
-                    (String::from(line) + "\n", true)
-                } else {
-                    (String::from(line), true)
-                }
-            })
+            lines
+                .next()
+                .map_or((String::new(), false), |line| (String::from(line), true))
         };
         let mut next_line = shift_lines();
 
-        for mapping in generator.mappings.list.into_iter() {
+        for mapping in mappings {
             let generated_position = mapping.generated;
             if last_mapping.is_some() {
                 if last_generated_position.0 < generated_position.0 {
@@ -138,8 +351,8 @@ impl SourceNode {
                 } else {
                     let splitted = split_string(
                         next_line.0,
-                        generated_position.1 as i32 - last_generated_position.1 as i32,
-                        None
+                        generated_position.1.saturating_sub(last_generated_position.1),
+                        column_unit,
                     );
                     let code = splitted.0;
                     next_line.0 = splitted.1;
@@ -157,11 +370,7 @@ impl SourceNode {
                 last_generated_position.0 += 1;
             }
             if last_generated_position.1 < generated_position.1 {
-                let splitted = split_string(
-                    next_line.0,
-                    generated_position.1 as i32,
-                    None
-                );
+                let splitted = split_string(next_line.0, generated_position.1, column_unit);
                 node.add(Node::NString(splitted.0));
                 next_line.0 = splitted.1;
                 last_generated_position.1 = generated_position.1;
@@ -181,12 +390,29 @@ impl SourceNode {
             }
             node.add(Node::NString(remaining));
         }
-        node.source_contents = generator.sources_contents;
+        for source in consumer.sources() {
+            if let Some(content) = consumer.source_content_for(source) {
+                node.source_contents.insert(resolved[source].clone(), content);
+            }
+            if consumer.is_ignored(source) {
+                node.ignored_sources.insert(resolved[source].clone());
+            }
+        }
         node
     }
 
+    // takes the map by value since the consumer is built from it and dropped afterwards
+    pub fn from_string_with_src_map(
+        code: &str,
+        source_map: SrcMap,
+        relative_path: Option<&str>,
+    ) -> Result<SourceNode, Error> {
+        let consumer = SourceMapConsumer::new(source_map)?;
+        Ok(SourceNode::from_string_with_source_map(code, &consumer, relative_path))
+    }
+
     fn add_mapping_with_code(&mut self, mapping: Option<Mapping>, code: String) {
-        let is_original = mapping.as_ref().map_or(false, |mapping| mapping.source.is_some());
+        let is_original = mapping.as_ref().is_some_and(|mapping| mapping.source.is_some());
         if !is_original {
             self.add(Node::NString(code));
         } else {
@@ -194,8 +420,8 @@ impl SourceNode {
             self.add(Node::NSourceNode(
                 SourceNode::new(
                     mapping.original,
-                    mapping.source.map(|sp| StringPtr::Ptr(sp)),
-                    mapping.name.map(|sp| StringPtr::Ptr(sp)),
+                    mapping.source.map(StringPtr::Ptr),
+                    mapping.name.map(StringPtr::Ptr),
                     Some(Node::NString(code))
                 )
             ));
@@ -206,21 +432,27 @@ impl SourceNode {
 struct ToSourceMapContext {
     pub map: SourceMapGenerator,
     source_mapping_active: bool,
-    last_original_source: Option<Rc<String>>,
+    last_original_source: Option<Ptr<String>>,
     last_original_position: Option<(usize, usize)>,
-    last_original_name: Option<Rc<String>>,
+    last_original_name: Option<Ptr<String>>,
     generated_code: String,
     generated_position: (usize, usize),
+    column_unit: ColumnUnit,
+    line_terminators: LineTerminators,
+    // the last chunk ended with a `\r`, a `\n` starting the next one belongs to it
+    after_cr: bool,
 }
 
 impl ToSourceMapContext {
     pub fn new(
-        file: Option<Rc<String>>,
-        source_root: Option<Rc<String>>,
+        file: Option<Ptr<String>>,
+        source_root: Option<Ptr<String>>,
         skip_validation: bool,
+        column_unit: ColumnUnit,
+        line_terminators: LineTerminators,
     ) -> ToSourceMapContext {
-        let file = file.map(|sp| StringPtr::Ptr(sp));
-        let source_root = source_root.map(|sp| StringPtr::Ptr(sp));
+        let file = file.map(StringPtr::Ptr);
+        let source_root = source_root.map(StringPtr::Ptr);
         ToSourceMapContext {
             map: SourceMapGenerator::new(file, source_root, skip_validation),
             source_mapping_active: false,
@@ -229,48 +461,65 @@ impl ToSourceMapContext {
             last_original_name: None,
             generated_code: String::new(),
             generated_position: (1, 0),
+            column_unit,
+            line_terminators,
+            after_cr: false,
         }
     }
 }
 
-impl WalkFunction for ToSourceMapContext {
-    fn process_chunk(
+impl SourceNodeVisitor for ToSourceMapContext {
+    fn visit_chunk(
         &mut self,
-        chunk: &Rc<String>,
-        original_source: &Option<Rc<String>>,
-        original_position: &Option<(usize, usize)>,
-        original_name: &Option<Rc<String>>,
+        chunk: &str,
+        original_source: Option<&Ptr<String>>,
+        original_position: Option<(usize, usize)>,
+        original_name: Option<&Ptr<String>>,
     ) {
+        let original_source = original_source.cloned();
+        let original_name = original_name.cloned();
         self.generated_code += chunk;
         if original_source.is_some() && original_position.is_some() {
-            if self.last_original_source != *original_source
-                || self.last_original_position != *original_position
-                || self.last_original_name != *original_name
+            if self.last_original_source != original_source
+                || self.last_original_position != original_position
+                || self.last_original_name != original_name
             {
-                self.map.add_mapping(Mapping {
+                self.map.push_mapping(Mapping {
                     source: original_source.clone(),
-                    original: original_position.clone(),
-                    generated: self.generated_position.clone(),
+                    original: original_position,
+                    generated: self.generated_position,
                     name: original_name.clone(),
+                    range: false,
                 });
             }
             self.last_original_source = original_source.clone();
-            self.last_original_position = original_position.clone();
+            self.last_original_position = original_position;
             self.last_original_name = original_name.clone();
             self.source_mapping_active = true;
         } else if self.source_mapping_active {
-            self.map.add_mapping(Mapping {
+            self.map.push_mapping(Mapping {
                 source: None,
                 original: None,
-                generated: self.generated_position.clone(),
+                generated: self.generated_position,
                 name: None,
+                range: false,
             });
             self.last_original_source = None;
             self.source_mapping_active = false;
         }
         let mut chars = chunk.chars().peekable();
         while let Some(c) = chars.next() {
-            if c == '\n' {
+            if mem::replace(&mut self.after_cr, false) && c == '\n' {
+                continue;
+            }
+            if self.line_terminators.is_terminator(c) {
+                if c == '\r' {
+                    if chars.peek() == Some(&'\n') {
+                        chars.next();
+                    } else {
+                        self.after_cr = true;
+                    }
+                }
                 self.generated_position.0 += 1; // line++
                 self.generated_position.1 = 0; // column = 0
 
@@ -278,49 +527,62 @@ impl WalkFunction for ToSourceMapContext {
                     self.last_original_source = None;
                     self.source_mapping_active = false;
                 } else if self.source_mapping_active {
-                    self.map.add_mapping(Mapping {
+                    self.map.push_mapping(Mapping {
                         source: original_source.clone(),
-                        original: original_position.clone(),
-                        generated: self.generated_position.clone(),
+                        original: original_position,
+                        generated: self.generated_position,
                         name: original_name.clone(),
+                        range: false,
                     })
                 }
             } else {
-                self.generated_position.1 += 1; // column++
+                self.generated_position.1 += self.column_unit.char_width(c); // column++
             }
         }
     }
 
-    fn process_source_content(&mut self, source: &Rc<String>, source_content: &Rc<String>) {
+    fn visit_source_content(&mut self, source: &Ptr<String>, source_content: &Ptr<String>) {
         self.map.set_source_content(
             StringPtr::Ptr(source.clone()),
             Some(StringPtr::Ptr(source_content.clone())),
         );
     }
+
+    fn visit_ignored_source(&mut self, source: &Ptr<String>) {
+        self.map.set_source_ignored(StringPtr::Ptr(source.clone()), true);
+    }
 }
 
-trait WalkFunction {
-    fn process_chunk(
+pub trait SourceNodeVisitor {
+    // `source`, `position` and `name` are the ones of the node holding the chunk
+    fn visit_chunk(
         &mut self,
-        chunk: &Rc<String>,
-        original_source: &Option<Rc<String>>,
-        original_position: &Option<(usize, usize)>,
-        original_name: &Option<Rc<String>>,
+        chunk: &str,
+        source: Option<&Ptr<String>>,
+        position: Option<(usize, usize)>,
+        name: Option<&Ptr<String>>,
     );
-    fn process_source_content(&mut self, source: &Rc<String>, source_content: &Rc<String>);
+
+    fn visit_source_content(&mut self, _source: &Ptr<String>, _source_content: &Ptr<String>) {}
+
+    fn visit_ignored_source(&mut self, _source: &Ptr<String>) {}
 }
 
-#[inline]
-fn split_string(mut s: String, pos: i32, s_len: Option<usize>) -> (String, String) {
-    let s_len = s_len.map_or(s.chars().count(), |l| l);
-
-    if pos <= 0 {
-        (String::new(), s)
-    } else if pos >= s_len as i32 {
-        (s, String::new())
-    } else {
-        let pos = s.char_indices().skip(pos as usize).next().unwrap().0;
-        let off = s.split_off(pos);
-        (s, off)
+impl fmt::Display for SourceNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut result = Ok(());
+        self.walk(|chunk, _, _, _| {
+            if result.is_ok() {
+                result = f.write_str(chunk);
+            }
+        });
+        result
     }
 }
+
+#[inline]
+fn split_string(mut s: String, pos: usize, column_unit: ColumnUnit) -> (String, String) {
+    let pos = column_unit.byte_offset(&s, pos);
+    let off = s.split_off(pos);
+    (s, off)
+}
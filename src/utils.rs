@@ -1,4 +1,35 @@
+use error::Error;
 use regex::Regex;
+use source_map_consumer::Bias;
+use source_map_mappings::{self, Mappings as _Mappings};
+use std::collections::HashSet;
+use vlq;
+
+// appends `line` to `code`, starting a new line unless `code` is empty or already ends with one
+pub fn push_line(code: &mut String, line: &str) {
+    if !code.is_empty() && !code.ends_with('\n') {
+        code.push('\n');
+    }
+    *code += line;
+}
+
+// decodes `%XX` escapes, `None` when one is malformed or the result is not UTF-8
+pub fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3).filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
 
 pub fn relative(root: &str, path: &str) -> String {
     let mut root = String::from(root);
@@ -10,9 +41,9 @@ pub fn relative(root: &str, path: &str) -> String {
     }
 
     let mut level = 0;
-    while let Some(_) = path.find(&(root.clone() + "/")) {
+    while !path.starts_with(&(root.clone() + "/")) {
         if let Some(index) = root.rfind('/') {
-            root.split_off(index);
+            root.truncate(index);
             lazy_static! {
                 static ref RE: Regex = Regex::new("^([^/]+:/)?/*$").unwrap();
             }
@@ -26,3 +57,402 @@ pub fn relative(root: &str, path: &str) -> String {
     }
     "../".repeat(level) + path.split_at(root.len() + 1).1
 }
+
+lazy_static! {
+    static ref URL_RE: Regex =
+        Regex::new(r"^(?:([\w+\-.]+):)?//(?:(\w+:\w+)@)?([\w.-]*)(?::(\d+))?(.*)$").unwrap();
+    static ref DATA_URL_RE: Regex = Regex::new(r"^data:.+,.+$").unwrap();
+}
+
+struct Url {
+    scheme: String,
+    auth: String,
+    host: String,
+    port: String,
+    path: String,
+}
+
+fn url_parse(url: &str) -> Option<Url> {
+    URL_RE.captures(url).map(|caps| {
+        let get = |i| caps.get(i).map_or(String::new(), |m| String::from(m.as_str()));
+        Url {
+            scheme: get(1),
+            auth: get(2),
+            host: get(3),
+            port: get(4),
+            path: get(5),
+        }
+    })
+}
+
+fn url_generate(url: &Url) -> String {
+    let mut result = String::new();
+    if !url.scheme.is_empty() {
+        result += &url.scheme;
+        result.push(':');
+    }
+    result += "//";
+    if !url.auth.is_empty() {
+        result += &url.auth;
+        result.push('@');
+    }
+    result += &url.host;
+    if !url.port.is_empty() {
+        result.push(':');
+        result += &url.port;
+    }
+    result += &url.path;
+    result
+}
+
+pub fn is_absolute(path: &str) -> bool {
+    path.starts_with('/') || URL_RE.is_match(path)
+}
+
+pub fn normalize(path: &str) -> String {
+    let mut url = url_parse(path);
+    let target = match url {
+        Some(ref url) if url.path.is_empty() => return String::from(path),
+        Some(ref url) => url.path.clone(),
+        None => String::from(path),
+    };
+    let absolute = is_absolute(&target);
+
+    lazy_static! {
+        static ref SLASHES: Regex = Regex::new("/+").unwrap();
+    }
+    let mut parts: Vec<&str> = SLASHES.split(&target).collect();
+    let mut up = 0;
+    let mut i = parts.len();
+    while i > 0 {
+        i -= 1;
+        let part = parts[i];
+        if part == "." {
+            parts.remove(i);
+        } else if part == ".." {
+            up += 1;
+        } else if up > 0 {
+            if part.is_empty() {
+                let end = (i + 1 + up).min(parts.len());
+                parts.drain(i + 1..end);
+                up = 0;
+            } else {
+                parts.drain(i..i + 2);
+                up -= 1;
+            }
+        }
+    }
+
+    let mut normalized = parts.join("/");
+    if normalized.is_empty() {
+        normalized = String::from(if absolute { "/" } else { "." });
+    }
+    if let Some(ref mut url) = url {
+        url.path = normalized;
+        return url_generate(url);
+    }
+    normalized
+}
+
+pub fn join(root: &str, path: &str) -> String {
+    let root = if root.is_empty() { "." } else { root };
+    let path = if path.is_empty() { "." } else { path };
+
+    let path_url = url_parse(path);
+    let mut root_url = url_parse(root);
+    let root = match root_url {
+        Some(ref url) if url.path.is_empty() => String::from("/"),
+        Some(ref url) => url.path.clone(),
+        None => String::from(root),
+    };
+
+    // `join(foo, '//www.example.org')`
+    if let Some(mut path_url) = path_url {
+        if path_url.scheme.is_empty() {
+            if let Some(ref root_url) = root_url {
+                path_url.scheme = root_url.scheme.clone();
+            }
+            return url_generate(&path_url);
+        }
+        return String::from(path);
+    }
+    if DATA_URL_RE.is_match(path) {
+        return String::from(path);
+    }
+
+    // `join('http://', 'www.example.com')`
+    if let Some(ref mut url) = root_url {
+        if url.host.is_empty() && url.path.is_empty() {
+            url.host = String::from(path);
+            return url_generate(url);
+        }
+    }
+
+    let joined = if path.starts_with('/') {
+        String::from(path)
+    } else {
+        normalize(&(String::from(root.trim_end_matches('/')) + "/" + path))
+    };
+
+    if let Some(ref mut url) = root_url {
+        url.path = joined;
+        return url_generate(url);
+    }
+    joined
+}
+
+// originate from `binarySearch.search`
+pub fn binary_search<T, K, F>(list: &[T], needle: &K, key: F, bias: Bias) -> Option<usize>
+where
+    K: Ord,
+    F: Fn(&T) -> K,
+{
+    let idx = match list.binary_search_by(|item| key(item).cmp(needle)) {
+        Ok(idx) => idx,
+        Err(idx) => match bias {
+            Bias::LeastUpperBound if idx < list.len() => idx,
+            Bias::GreatestLowerBound if idx > 0 => idx - 1,
+            _ => return None,
+        },
+    };
+
+    // walk back to the lowest index of equal elements
+    let mut idx = idx;
+    while idx > 0 && key(&list[idx - 1]) == key(&list[idx]) {
+        idx -= 1;
+    }
+    Some(idx)
+}
+
+const BASE64_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// `rangeMappings` holds, for each generated line, the indices of the line's segments in
+// `mappings` that are range mappings, as unsigned VLQ deltas from the previous index
+// (starting at -1)
+pub fn parse_range_mappings(range_mappings: &str) -> Result<Vec<Vec<usize>>, Error> {
+    let bytes = range_mappings.as_bytes();
+    let mut lines = vec![Vec::new()];
+    let mut previous: Option<usize> = None;
+    let mut offset = 0;
+    while offset < bytes.len() {
+        if bytes[offset] == b';' {
+            lines.push(Vec::new());
+            previous = None;
+            offset += 1;
+            continue;
+        }
+        let start = offset;
+        let mut value: usize = 0;
+        let mut shift = 0;
+        loop {
+            let digit = match bytes.get(offset).and_then(|&b| BASE64_CHARS.iter().position(|&c| c == b)) {
+                Some(digit) if shift < 60 => digit,
+                _ => return Err(Error::InvalidRangeMappings(start)),
+            };
+            value |= (digit & 31) << shift;
+            shift += 5;
+            offset += 1;
+            if digit & 32 == 0 {
+                break;
+            }
+        }
+        if value == 0 {
+            return Err(Error::InvalidRangeMappings(start));
+        }
+        let idx = previous.map_or(value - 1, |previous| previous + value);
+        lines.last_mut().unwrap().push(idx);
+        previous = Some(idx);
+    }
+    Ok(lines)
+}
+
+pub fn serialize_range_mappings(lines: &[Vec<usize>]) -> String {
+    let mut result = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            result.push(';');
+        }
+        let mut previous: Option<usize> = None;
+        for &idx in line {
+            let mut value = previous.map_or(idx + 1, |previous| idx - previous);
+            loop {
+                let mut digit = value & 31;
+                value >>= 5;
+                if value > 0 {
+                    digit |= 32;
+                }
+                result.push(BASE64_CHARS[digit] as char);
+                if value == 0 {
+                    break;
+                }
+            }
+            previous = Some(idx);
+        }
+    }
+    result
+}
+
+pub fn is_range_mapping(range_mappings: &[Vec<usize>], line: usize, idx: usize) -> bool {
+    range_mappings
+        .get(line)
+        .is_some_and(|line| line.binary_search(&idx).is_ok())
+}
+
+// generated column of a segment, then its source index, original line, original column
+// and name index when it is mapped, lines being 0-based as stored
+pub type Segment = (usize, Option<(usize, usize, usize, Option<usize>)>);
+
+pub fn segment(mapping: &source_map_mappings::Mapping) -> Segment {
+    let original = mapping.original.as_ref().map(|original| {
+        (
+            original.source as usize,
+            original.original_line as usize,
+            original.original_column as usize,
+            original.name.map(|idx| idx as usize),
+        )
+    });
+    (mapping.generated_column as usize, original)
+}
+
+// decodes the segments of the generated line `mappings[start..end]` in their encoded
+// order; `state` holds the source, original line, original column and name the line
+// starts from, which are encoded relative to the previous segment across lines
+pub fn decode_line<F>(mappings: &[u8], start: usize, end: usize, state: &mut [i64; 4], mut f: F) -> Result<(), Error>
+where
+    F: FnMut(Segment),
+{
+    let mut column: i64 = 0;
+    let mut offset = start;
+    while offset < end {
+        if mappings[offset] == b',' {
+            offset += 1;
+            continue;
+        }
+        let segment = offset;
+        let mut fields = [0i64; 5];
+        let mut len = 0;
+        while offset < end && mappings[offset] != b',' {
+            if len == fields.len() {
+                return Err(Error::InvalidVlq(segment));
+            }
+            let mut input = mappings[offset..end].iter().cloned();
+            fields[len] = vlq::decode(&mut input).map_err(|_| Error::InvalidVlq(segment))?;
+            offset = end - input.len();
+            len += 1;
+        }
+        if len == 2 || len == 3 {
+            return Err(Error::InvalidVlq(segment));
+        }
+
+        column += fields[0];
+        for i in 1..len {
+            state[i - 1] += fields[i];
+        }
+        if column < 0 || state.iter().any(|&value| value < 0) {
+            return Err(Error::InvalidVlq(segment));
+        }
+        let original = if len == 1 {
+            None
+        } else {
+            let name = if len == 5 { Some(state[3] as usize) } else { None };
+            Some((state[0] as usize, state[1] as usize, state[2] as usize, name))
+        };
+        f((column as usize, original));
+    }
+    Ok(())
+}
+
+// the mapped segments `range_mappings` marks, with their 0-based generated line; the
+// indices count segments in encoded order, which sorting by generated position loses
+pub fn range_segments(mappings: &str, range_mappings: &[Vec<usize>]) -> Result<HashSet<(usize, Segment)>, Error> {
+    let mut segments = HashSet::new();
+    let mut state = [0; 4];
+    let mut start = 0;
+    for (line, text) in mappings.split(';').enumerate().take(range_mappings.len()) {
+        let end = start + text.len();
+        let mut idx = 0;
+        decode_line(mappings.as_bytes(), start, end, &mut state, |segment| {
+            if segment.1.is_some() && is_range_mapping(range_mappings, line, idx) {
+                segments.insert((line, segment));
+            }
+            idx += 1;
+        })?;
+        start = end + 1;
+    }
+    Ok(segments)
+}
+
+// 0-based generated line and greatest column of the segments on the last line of
+// `mappings` having any; generated columns restart on each line, so only that line is decoded
+pub fn last_generated_position(mappings: &str) -> Result<Option<(usize, usize)>, Error> {
+    let trimmed = mappings.trim_end_matches([';', ',']);
+    let start = trimmed.rfind(';').map_or(0, |idx| idx + 1);
+    let line = trimmed.as_bytes()[..start].iter().filter(|&&b| b == b';').count();
+    let bytes = trimmed.as_bytes();
+    let mut column: i64 = 0;
+    let mut last = None;
+    let mut offset = start;
+    while offset < bytes.len() {
+        if bytes[offset] == b',' {
+            offset += 1;
+            continue;
+        }
+        let mut input = bytes[offset..].iter().cloned();
+        column += vlq::decode(&mut input).map_err(|_| Error::InvalidVlq(offset))?;
+        if column < 0 {
+            return Err(Error::InvalidVlq(offset));
+        }
+        last = last.max(Some(column as usize));
+        // the other fields of the segment do not matter here
+        offset = bytes[offset..].iter().position(|&b| b == b',').map_or(bytes.len(), |idx| offset + idx);
+    }
+    Ok(last.map(|column| (line, column)))
+}
+
+pub fn parse_mappings(mappings: &str) -> Result<_Mappings<()>, Error> {
+    source_map_mappings::parse_mappings(mappings.as_bytes())
+        .map_err(|_| Error::InvalidVlq(invalid_segment_offset(mappings.as_bytes())))
+}
+
+// `parse_mappings` does not report where it failed, so the segments are decoded
+// again to find the first one that is malformed or holds an out of range value
+fn invalid_segment_offset(mappings: &[u8]) -> usize {
+    // generated column, source, original line, original column, name
+    let mut previous: [i64; 5] = [0; 5];
+    let mut offset = 0;
+    while offset < mappings.len() {
+        match mappings[offset] {
+            b';' => {
+                previous[0] = 0;
+                offset += 1;
+            }
+            b',' => {
+                offset += 1;
+            }
+            _ => {
+                let start = offset;
+                let mut fields = 0;
+                while offset < mappings.len() && mappings[offset] != b',' && mappings[offset] != b';' {
+                    let mut input = mappings[offset..].iter().cloned();
+                    let value = match vlq::decode(&mut input) {
+                        Ok(value) => value,
+                        Err(_) => return start,
+                    };
+                    offset = mappings.len() - input.len();
+                    if fields >= previous.len() {
+                        return start;
+                    }
+                    previous[fields] += value;
+                    if previous[fields] < 0 || previous[fields] > i64::from(u32::MAX) {
+                        return start;
+                    }
+                    fields += 1;
+                }
+                if fields == 2 || fields == 3 {
+                    return start;
+                }
+            }
+        }
+    }
+    mappings.len()
+}
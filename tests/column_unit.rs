@@ -0,0 +1,129 @@
+extern crate source_map;
+
+#[cfg(test)]
+mod column_unit {
+    use source_map::*;
+
+    fn node() -> SourceNode {
+        let mut node = SourceNode::new(None, None, None, None);
+        node.add(Node::NSourceNode(SourceNode::new(
+            Some((1, 0)),
+            Some(StringPtr::Str(String::from("a.js"))),
+            None,
+            Some(Node::NString(String::from("\u{1F600}"))),
+        )));
+        node.add(Node::NSourceNode(SourceNode::new(
+            Some((1, 5)),
+            Some(StringPtr::Str(String::from("a.js"))),
+            None,
+            Some(Node::NString(String::from("x"))),
+        )));
+        node
+    }
+
+    fn consumer(mappings: &str) -> SourceMapConsumer {
+        SourceMapConsumer::new(SrcMap {
+            version: 3,
+            sources: vec![String::from("a.js")],
+            sources_content: vec![Some(String::from("\u{1F600}   x"))],
+            mappings: String::from(mappings),
+            ..SrcMap::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn convert() {
+        let line = "a\u{1F600}b";
+        assert_eq!(ColumnUnit::Utf16.width(line), 4);
+        assert_eq!(ColumnUnit::Char.width(line), 3);
+        assert_eq!(ColumnUnit::Byte.width(line), 6);
+        assert_eq!(convert_column(line, 2, ColumnUnit::Char, ColumnUnit::Utf16), 3);
+        assert_eq!(convert_column(line, 3, ColumnUnit::Utf16, ColumnUnit::Byte), 5);
+        // inside the surrogate pair
+        assert_eq!(convert_column(line, 2, ColumnUnit::Utf16, ColumnUnit::Char), 2);
+        // past the end of the line
+        assert_eq!(convert_column(line, 5, ColumnUnit::Char, ColumnUnit::Byte), 8);
+        assert_eq!(ColumnUnit::Utf16.byte_offset(line, 3), 5);
+        assert_eq!(ColumnUnit::Utf16.column_at(line, 5), 3);
+    }
+
+    #[test]
+    fn generation_defaults_to_utf16() {
+        let result = node().to_string_with_source_map(None, None).unwrap();
+        assert_eq!(result.map.mappings, "AAAA,EAAK");
+        let result = node()
+            .to_string_with_source_map_in(None, None, ColumnUnit::Char, LineTerminators::Lf)
+            .unwrap();
+        assert_eq!(result.map.mappings, "AAAA,CAAK");
+        let result = node()
+            .to_string_with_source_map_in(None, None, ColumnUnit::Byte, LineTerminators::Lf)
+            .unwrap();
+        assert_eq!(result.map.mappings, "AAAA,IAAK");
+    }
+
+    #[test]
+    fn from_string_splits_on_consumer_unit() {
+        let code = "\u{1F600}x";
+        let mut bytes = consumer("AAAA,IAAK");
+        bytes.set_column_unit(ColumnUnit::Byte);
+        for consumer in &[consumer("AAAA,EAAK"), bytes] {
+            let node = SourceNode::from_string_with_source_map(code, consumer, None);
+            let mut chunks = Vec::new();
+            node.walk(|chunk, _, position, _| chunks.push((String::from(chunk), position)));
+            assert_eq!(chunks[0], (String::from("\u{1F600}"), Some((1, 0))));
+            assert_eq!(chunks[1], (String::from("x"), Some((1, 5))));
+        }
+    }
+
+    #[test]
+    fn lookups() {
+        let consumer = consumer("AAAA,EAAK");
+        let mapping =
+            consumer.original_position_for_in("\u{1F600}x", 1, 1, ColumnUnit::Char, Bias::GreatestLowerBound);
+        assert_eq!(mapping.generated, (1, 1));
+        assert_eq!(mapping.original, Some((1, 4)));
+
+        let mapping =
+            consumer.generated_position_for_in("a.js", 1, 4, ColumnUnit::Char, Bias::GreatestLowerBound);
+        assert_eq!(mapping.generated, (1, 2));
+        assert_eq!(mapping.original, Some((1, 4)));
+    }
+
+    #[test]
+    fn lookups_landing_on_another_original_line() {
+        let consumer = SourceMapConsumer::new(SrcMap {
+            sources: vec![String::from("a.js")],
+            sources_content: vec![Some(String::from("ab\n\u{1F600}y"))],
+            mappings: String::from("AAAA,IACE"),
+            ..SrcMap::default()
+        })
+        .unwrap();
+        let mapping = consumer.generated_position_for_in("a.js", 1, 5, ColumnUnit::Char, Bias::LeastUpperBound);
+        assert_eq!(mapping.generated, (1, 4));
+        assert_eq!(mapping.original, Some((2, 1)));
+    }
+
+    #[test]
+    fn lazy_lookups() {
+        let map = || SrcMap {
+            sources: vec![String::from("a.js")],
+            sources_content: vec![Some(String::from("\u{1F600}   x"))],
+            mappings: String::from("AAAA,IAAI"),
+            ..SrcMap::default()
+        };
+        let mut eager = SourceMapConsumer::new(map()).unwrap();
+        let mut lazy = LazySourceMapConsumer::new(map()).unwrap();
+        eager.set_column_unit(ColumnUnit::Byte);
+        lazy.set_column_unit(ColumnUnit::Byte);
+        assert_eq!(lazy.column_unit(), ColumnUnit::Byte);
+        let line = "\u{1F600}x";
+        for column in 0..3 {
+            let expected = eager.original_position_for_in(line, 1, column, ColumnUnit::Char, Bias::GreatestLowerBound);
+            let found = lazy.original_position_for_in(line, 1, column, ColumnUnit::Char, Bias::GreatestLowerBound);
+            assert_eq!(found.unwrap(), expected);
+        }
+        let mapping = lazy.original_position_for_in(line, 1, 1, ColumnUnit::Char, Bias::GreatestLowerBound);
+        assert_eq!(mapping.unwrap().original, Some((1, 1)));
+    }
+}
@@ -0,0 +1,62 @@
+extern crate source_map;
+
+#[cfg(test)]
+mod debug_id {
+    use source_map::*;
+
+    fn node() -> SourceNode {
+        SourceNode::new(
+            Some((1, 0)),
+            Some(StringPtr::Str(String::from("a.js"))),
+            None,
+            Some(Node::NString(String::from("var a = 1;"))),
+        )
+    }
+
+    #[test]
+    fn deterministic_id_and_comment() {
+        let first = node().to_string_with_debug_id(None, None).unwrap();
+        let second = node().to_string_with_debug_id(None, None).unwrap();
+        let id = first.map.debug_id.clone().unwrap();
+        assert_eq!(second.map.debug_id, Some(id.clone()));
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert_eq!(first.source, format!("var a = 1;\n//# debugId={}", id));
+        assert_eq!(parse_debug_id(&first.source), Some(id.clone()));
+
+        let plain = node().to_string_with_source_map(None, None).unwrap();
+        assert_eq!(generate_debug_id(&plain.source, &first.map).unwrap(), id);
+        assert_ne!(generate_debug_id("var b = 1;", &plain.map).unwrap(), id);
+    }
+
+    #[test]
+    fn parses_last_comment() {
+        let code = concat!(
+            "a();\n",
+            "//# debugId=00000000-0000-4000-8000-000000000001\n",
+            "//# sourceMappingURL=a.js.map\n",
+            "/*# debugId=85314830-023F-4CF1-A267-535F4E37BB17 */\n"
+        );
+        assert_eq!(
+            parse_debug_id(code),
+            Some(String::from("85314830-023f-4cf1-a267-535f4e37bb17"))
+        );
+        assert_eq!(parse_debug_id("var debugId = 1;"), None);
+    }
+
+    #[test]
+    fn json_field() {
+        let json = r#"{"version":3,"sources":[],"names":[],"mappings":"","debugId":"85314830-023f-4cf1-a267-535f4e37bb17"}"#;
+        let map = SrcMap::from_json(json).unwrap();
+        assert_eq!(map.debug_id, Some(String::from("85314830-023f-4cf1-a267-535f4e37bb17")));
+        assert_eq!(map.to_json().unwrap(), json);
+
+        let consumer = SourceMapConsumer::from_json(json).unwrap();
+        assert_eq!(consumer.debug_id().map(|id| id.as_str()), map.debug_id.as_deref());
+
+        let legacy = r#"{"version":3,"sources":[],"mappings":"","debug_id":"abc"}"#;
+        assert_eq!(SrcMap::from_json(legacy).unwrap().debug_id, Some(String::from("abc")));
+        let mut generator = from_source_map(json, false).unwrap();
+        assert_eq!(generator.to_source_map().unwrap().debug_id, map.debug_id);
+    }
+}
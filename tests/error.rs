@@ -0,0 +1,111 @@
+extern crate source_map;
+
+#[cfg(test)]
+mod error {
+    use source_map::*;
+
+    #[test]
+    fn invalid_json() {
+        match SourceMapConsumer::from_json("{\"version\": 3,") {
+            Err(Error::Json(_)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        match from_source_map("[]", false) {
+            Err(Error::Json(_)) => {}
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn invalid_vlq_reports_offset() {
+        match SourceMapConsumer::new(src_map("AAAA;AA!A", 1, 0)) {
+            Err(Error::InvalidVlq(5)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        // original line becomes negative
+        match SourceMapConsumer::new(src_map("AAAA,CADA", 1, 0)) {
+            Err(Error::InvalidVlq(5)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn index_out_of_range() {
+        match SourceMapConsumer::new(src_map("AAAA;ACAA", 1, 0)) {
+            Err(Error::SourceOutOfRange(1)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        match SourceMapConsumer::new(src_map("AAAAC", 1, 1)) {
+            Err(Error::NameOutOfRange(1)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let json = r#"{"version":2,"sources":["a.js"],"names":[],"mappings":"AAAA,EAAE"}"#;
+        match SourceMapConsumer::from_json(json) {
+            Err(Error::UnsupportedVersion(2)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        match LazySourceMapConsumer::from_json(json) {
+            Err(Error::UnsupportedVersion(2)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // lenient mode parses the map and the consumers use it as is
+        let source_map = SrcMap::from_json_lenient(json).unwrap();
+        assert_eq!(source_map.version, 2);
+        let consumer = SourceMapConsumer::new(source_map.clone()).unwrap();
+        let found = consumer.original_position_for(1, 3, Bias::GreatestLowerBound);
+        assert_eq!(found.original, Some((1, 2)));
+        let mut lazy = LazySourceMapConsumer::new(source_map).unwrap();
+        assert_eq!(lazy.original_position_for(1, 3, Bias::GreatestLowerBound).unwrap(), found);
+    }
+
+    #[test]
+    fn invalid_mapping() {
+        let mut generator = SourceMapGenerator::new(None, None, false);
+        let mapping = Mapping {
+            generated: (1, 0),
+            source: Some(Ptr::new(String::from("a.js"))),
+            name: None,
+            original: None,
+            range: false,
+        };
+        match generator.add_mapping(mapping.clone()) {
+            Err(Error::InvalidMapping(ref invalid)) if *invalid == mapping => {}
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let mut generator = SourceMapGenerator::new(None, None, true);
+        generator.add_mapping(mapping.clone()).unwrap();
+        match generator.to_source_map() {
+            Err(Error::InvalidMapping(ref invalid)) if *invalid == mapping => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_source_file() {
+        let consumer = SourceMapConsumer::new(src_map("AAAA", 1, 0)).unwrap();
+        let mut generator = SourceMapGenerator::new(None, None, false);
+        match generator.apply_source_map(&consumer, None, None) {
+            Err(Error::MissingSourceFile) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    fn src_map(mappings: &str, sources: usize, names: usize) -> SrcMap {
+        SrcMap {
+            version: 3,
+            file: None,
+            source_root: None,
+            sources: (0..sources).map(|i| format!("{}.js", i)).collect(),
+            sources_content: Vec::new(),
+            names: (0..names).map(|i| format!("name{}", i)).collect(),
+            mappings: String::from(mappings),
+            ..SrcMap::default()
+        }
+    }
+}
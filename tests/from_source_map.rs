@@ -0,0 +1,18 @@
+extern crate source_map;
+
+#[cfg(test)]
+mod from_source_map {
+    use source_map::*;
+
+    #[test]
+    fn lines_are_one_based() {
+        let json = r#"{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA;AACE"}"#;
+        let mut generator = from_source_map(json, false).unwrap();
+
+        let mapping = generator.original_position_for(1, 0);
+        assert_eq!(mapping.original, Some((1, 0)));
+        let mapping = generator.original_position_for(2, 0);
+        assert_eq!(mapping.original, Some((2, 2)));
+        assert_eq!(generator.to_source_map().unwrap().mappings, "AAAA;AACE");
+    }
+}
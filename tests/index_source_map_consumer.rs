@@ -0,0 +1,125 @@
+extern crate source_map;
+
+#[cfg(test)]
+mod index_source_map_consumer {
+    use source_map::*;
+
+    #[test]
+    fn mapping_tokens_back_exactly() {
+        let map = IndexedSourceMapConsumer::new(indexed_test_map()).unwrap();
+        assert_mapping(&map, 1, 1, "/the/root/one.js", 1, 1, None);
+        assert_mapping(&map, 1, 18, "/the/root/one.js", 1, 21, Some("bar"));
+        assert_mapping(&map, 1, 28, "/the/root/one.js", 2, 10, Some("baz"));
+        assert_mapping(&map, 2, 1, "/the/root/two.js", 1, 1, None);
+        assert_mapping(&map, 2, 18, "/the/root/two.js", 1, 21, Some("n"));
+        assert_mapping(&map, 2, 28, "/the/root/two.js", 2, 10, Some("n"));
+
+        let lub = map.original_position_for(1, 33, Bias::LeastUpperBound);
+        assert_eq!(lub.source, None);
+    }
+
+    #[test]
+    fn column_offsets_within_a_line() {
+        let mut map = indexed_test_map();
+        map.sections[1].offset = Offset { line: 0, column: 40 };
+        let map = IndexedSourceMapConsumer::new(map).unwrap();
+
+        assert_mapping(&map, 1, 1, "/the/root/one.js", 1, 1, None);
+        assert_mapping(&map, 1, 41, "/the/root/two.js", 1, 1, None);
+        assert_mapping(&map, 1, 68, "/the/root/two.js", 2, 10, Some("n"));
+
+        let generated = map.generated_position_for("two.js", 2, 10, Bias::GreatestLowerBound);
+        assert_eq!(generated.generated, (1, 68));
+        let generated = map.generated_position_for("one.js", 2, 10, Bias::GreatestLowerBound);
+        assert_eq!(generated.generated, (1, 28));
+    }
+
+    #[test]
+    fn merged_sources_names_and_contents() {
+        let map = IndexedSourceMapConsumer::new(indexed_test_map()).unwrap();
+        let sources: Vec<&str> = map.sources().iter().map(|s| s.as_str()).collect();
+        assert_eq!(sources, ["/the/root/one.js", "/the/root/two.js"]);
+        let names: Vec<&str> = map.names().iter().map(|s| s.as_str()).collect();
+        assert_eq!(names, ["bar", "baz", "n"]);
+        assert!(map.has_contents_of_all_sources());
+        assert_eq!(
+            map.source_content_for("two.js").map(|s| (*s).clone()),
+            Some(String::from(" TWO.inc = function (n) {\n   return n + 1;\n };"))
+        );
+
+        let mut generated = Vec::new();
+        map.each_mapping(MappingOrder::Generated, |mapping| generated.push(mapping.generated));
+        assert_eq!(generated.len(), 13);
+        assert_eq!(generated[7], (2, 1));
+        assert!(generated.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn rejects_unordered_sections() {
+        for &(first, second) in &[((1, 0), (0, 0)), ((0, 5), (0, 5))] {
+            let mut map = indexed_test_map();
+            map.sections[0].offset = Offset { line: first.0, column: first.1 };
+            map.sections[1].offset = Offset { line: second.0, column: second.1 };
+            match IndexedSourceMapConsumer::new(map) {
+                // lines are reported 1-based
+                Err(Error::InvalidSectionOffset(line, column)) => {
+                    assert_eq!((line, column), (second.0 + 1, second.1))
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+
+    fn indexed_test_map() -> IndexSrcMap {
+        let section = |line, source: &str, content: &str, names: &[&str], mappings: &str| Section {
+            offset: Offset { line, column: 0 },
+            map: SrcMap {
+                version: 3,
+                file: Some(String::from("min.js")),
+                source_root: Some(String::from("/the/root")),
+                sources: vec![String::from(source)],
+                sources_content: vec![Some(String::from(content))],
+                names: names.iter().map(|s| String::from(*s)).collect(),
+                mappings: String::from(mappings),
+                ..SrcMap::default()
+            },
+        };
+        IndexSrcMap {
+            version: 3,
+            file: Some(String::from("min.js")),
+            sections: vec![
+                section(
+                    0,
+                    "one.js",
+                    " ONE.foo = function (bar) {\n   return baz(bar);\n };",
+                    &["bar", "baz"],
+                    "CAAC,IAAI,IAAM,SAAUA,GAClB,OAAOC,IAAID",
+                ),
+                section(
+                    1,
+                    "two.js",
+                    " TWO.inc = function (n) {\n   return n + 1;\n };",
+                    &["n"],
+                    "CAAC,IAAI,IAAM,SAAUA,GAClB,OAAOA",
+                ),
+            ],
+        }
+    }
+
+    fn assert_mapping(
+        map: &IndexedSourceMapConsumer,
+        generated_line: usize,
+        generated_column: usize,
+        source: &str,
+        original_line: usize,
+        original_column: usize,
+        name: Option<&str>,
+    ) {
+        let mapping =
+            map.original_position_for(generated_line, generated_column, Bias::GreatestLowerBound);
+        assert_eq!(mapping.generated, (generated_line, generated_column));
+        assert_eq!(mapping.source.map(|s| (*s).clone()), Some(String::from(source)));
+        assert_eq!(mapping.original, Some((original_line, original_column)));
+        assert_eq!(mapping.name.map(|s| (*s).clone()), name.map(String::from));
+    }
+}
@@ -0,0 +1,118 @@
+extern crate serde_json;
+extern crate source_map;
+
+#[cfg(test)]
+mod index_source_map_generator {
+    use serde_json;
+    use source_map::*;
+
+    #[test]
+    fn index_map_from_sections() {
+        let mut index = IndexedSourceMapGenerator::new(Some(StringPtr::Str(String::from("bundle.js"))));
+        index.add_generator((3, 0), &mut chunk("b.js", &[((1, 0), (1, 0)), ((2, 4), (2, 2))])).unwrap();
+        index.add_generator((1, 0), &mut chunk("a.js", &[((1, 0), (1, 0))])).unwrap();
+        let map = index.to_index_source_map();
+
+        assert_eq!(map.version, 3);
+        assert_eq!(map.file, Some(String::from("bundle.js")));
+        assert_eq!(
+            map.sections.iter().map(|section| section.offset).collect::<Vec<_>>(),
+            [Offset { line: 0, column: 0 }, Offset { line: 2, column: 0 }]
+        );
+        assert_eq!(map.sections[0].map.sources, ["a.js"]);
+        assert_eq!(map.sections[1].map.mappings, "AAAA;IACE");
+
+        let json = serde_json::to_value(&map).unwrap();
+        assert_eq!(json["sections"][1]["offset"]["line"], 2);
+        assert_eq!(json["sections"][1]["offset"]["column"], 0);
+    }
+
+    #[test]
+    fn flatten_into_regular_map() {
+        let mut index = IndexedSourceMapGenerator::new(None);
+        index.add_generator((1, 0), &mut chunk("a.js", &[((1, 0), (1, 0)), ((1, 6), (1, 4))])).unwrap();
+        index.add_generator((1, 10), &mut chunk("b.js", &[((1, 2), (3, 0)), ((2, 0), (4, 0))])).unwrap();
+        let flattened = index.to_source_map().unwrap();
+
+        let mut expected = chunk("a.js", &[((1, 0), (1, 0)), ((1, 6), (1, 4))]);
+        expected.add_mapping(Mapping {
+            generated: (1, 10),
+            source: None,
+            name: None,
+            original: None,
+            range: false,
+        }).unwrap();
+        for &(generated, original) in &[((1, 12), (3, 0)), ((2, 0), (4, 0))] {
+            expected.add_mapping(Mapping {
+                generated,
+                source: Some(Ptr::new(String::from("b.js"))),
+                name: None,
+                original: Some(original),
+                range: false,
+            }).unwrap();
+        }
+        let expected = expected.to_source_map().unwrap();
+
+        assert_eq!(flattened.sources, expected.sources);
+        assert_eq!(flattened.mappings, expected.mappings);
+        assert_eq!(flattened.mappings, "AAAA,MAAI,I,ECEJ;AACA");
+    }
+
+    #[test]
+    fn rejects_line_zero_and_overlapping_sections() {
+        let mut index = IndexedSourceMapGenerator::new(None);
+        match index.add_generator((0, 0), &mut chunk("a.js", &[((1, 0), (1, 0))])) {
+            Err(Error::InvalidSectionOffset(0, 0)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        // covers (2, 0) to (3, 4)
+        index.add_generator((2, 0), &mut chunk("a.js", &[((1, 0), (1, 0)), ((2, 4), (2, 0))])).unwrap();
+        for &offset in &[(2, 0), (3, 4), (1, 0)] {
+            let mut generator = chunk("b.js", &[((1, 0), (1, 0)), ((2, 0), (2, 0))]);
+            match index.add_generator(offset, &mut generator) {
+                Err(Error::InvalidSectionOffset(line, column)) => assert_eq!((line, column), offset),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        index.add_generator((1, 0), &mut chunk("b.js", &[((1, 0), (1, 0))])).unwrap();
+        index.add_generator((3, 5), &mut chunk("c.js", &[((1, 0), (1, 0))])).unwrap();
+        assert_eq!(index.to_index_source_map().sections.len(), 3);
+    }
+
+    #[test]
+    fn section_end_from_last_line_with_segments() {
+        let map = |mappings: &str| SrcMap {
+            sources: vec![String::from("a.js")],
+            mappings: String::from(mappings),
+            ..SrcMap::default()
+        };
+        let mut index = IndexedSourceMapGenerator::new(None);
+        // the last segments, on line 3, are out of order and end at column 5
+        index.add_section((1, 0), map("AAAA;;KAAA,FAAA;;")).unwrap();
+        match index.add_section((3, 5), map("AAAA")) {
+            Err(Error::InvalidSectionOffset(3, 5)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        index.add_section((3, 6), map("AAAA")).unwrap();
+        match index.add_section((5, 0), map("AAAA;!")) {
+            Err(Error::InvalidVlq(5)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    type Position = (usize, usize);
+
+    fn chunk(source: &str, mappings: &[(Position, Position)]) -> SourceMapGenerator {
+        let mut generator = SourceMapGenerator::new(None, None, false);
+        for &(generated, original) in mappings {
+            generator.add_mapping(Mapping {
+                generated,
+                source: Some(Ptr::new(String::from(source))),
+                name: None,
+                original: Some(original),
+                range: false,
+            }).unwrap();
+        }
+        generator
+    }
+}
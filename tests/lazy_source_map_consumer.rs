@@ -0,0 +1,113 @@
+extern crate source_map;
+
+#[cfg(test)]
+mod lazy_source_map_consumer {
+    use source_map::*;
+
+    #[test]
+    fn matches_eager_consumer() {
+        let eager = SourceMapConsumer::new(test_map()).unwrap();
+        let mut lazy = LazySourceMapConsumer::new(test_map()).unwrap();
+        // look lines up out of order so later lines get decoded first
+        for &line in &[4, 2, 1, 3, 5] {
+            for column in 0..40 {
+                for &bias in &[Bias::GreatestLowerBound, Bias::LeastUpperBound] {
+                    let expected = eager.original_position_for(line, column, bias);
+                    assert_eq!(lazy.original_position_for(line, column, bias).unwrap(), expected);
+                }
+            }
+        }
+        assert_eq!(lazy.mappings_for_line(3).unwrap().len(), 0);
+        assert_eq!(lazy.mappings_for_line(4).unwrap().len(), 5);
+        assert_eq!(lazy.source_content_for("/the/root/two.js"), eager.source_content_for("/the/root/two.js").as_ref().map(|content| content.as_str()));
+        assert_eq!(lazy.source_content_for("two.js"), eager.source_content_for("two.js").as_ref().map(|content| content.as_str()));
+        assert!(lazy.source_content_for("two.js").is_some());
+    }
+
+    #[test]
+    fn orders_equal_columns_like_eager_consumer() {
+        let map = || SrcMap {
+            sources: vec![String::from("a.js"), String::from("b.js")],
+            names: vec![String::from("x")],
+            // three segments at column 0 of line 1: b.js, a.js with a name and unmapped
+            mappings: String::from("ACAA,ADAAA,A;ACAA"),
+            ..SrcMap::default()
+        };
+        let eager = SourceMapConsumer::new(map()).unwrap();
+        let mut lazy = LazySourceMapConsumer::new(map()).unwrap();
+        let mut expected = Vec::new();
+        eager.each_mapping(MappingOrder::Generated, |mapping| expected.push(mapping.clone()));
+        let mut found = lazy.mappings_for_line(1).unwrap().to_vec();
+        found.extend_from_slice(lazy.mappings_for_line(2).unwrap());
+        assert_eq!(found, expected);
+        for &bias in &[Bias::GreatestLowerBound, Bias::LeastUpperBound] {
+            let mapping = lazy.original_position_for(1, 0, bias).unwrap();
+            assert_eq!(mapping, eager.original_position_for(1, 0, bias));
+            assert_eq!(mapping.source.unwrap().as_str(), "a.js");
+        }
+    }
+
+    #[test]
+    fn errors_only_for_decoded_lines() {
+        let map = SrcMap {
+            sources: vec![String::from("a.js")],
+            mappings: String::from("AAAA;AA!A;ACAA"),
+            ..SrcMap::default()
+        };
+        let mut lazy = LazySourceMapConsumer::new(map).unwrap();
+        let found = lazy.original_position_for(1, 0, Bias::GreatestLowerBound).unwrap();
+        assert_eq!(found.original, Some((1, 0)));
+        match lazy.original_position_for(2, 0, Bias::GreatestLowerBound) {
+            Err(Error::InvalidVlq(5)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(lazy.mappings_for_line(3).is_err());
+
+        let out_of_range = SrcMap {
+            sources: vec![String::from("a.js")],
+            mappings: String::from("AAAA;ACAA"),
+            ..SrcMap::default()
+        };
+        let mut lazy = LazySourceMapConsumer::new(out_of_range).unwrap();
+        assert!(lazy.mappings_for_line(1).is_ok());
+        match lazy.mappings_for_line(2) {
+            Err(Error::SourceOutOfRange(1)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn keeps_mappings_and_contents_borrowed() {
+        let json = r#"{"version":3,"sources":["a.js"],"sourcesContent":["let a = 1;"],"names":[],"mappings":"AAAA,IAAI"}"#;
+        let mut lazy = LazySourceMapConsumer::from_json(json).unwrap();
+        let content = lazy.source_content_for("a.js").unwrap();
+        let input = json.as_ptr() as usize..json.as_ptr() as usize + json.len();
+        assert!(input.contains(&(content.as_ptr() as usize)));
+        assert_eq!(content, "let a = 1;");
+        let found = lazy.original_position_for(1, 5, Bias::GreatestLowerBound).unwrap();
+        assert_eq!(found.original, Some((1, 4)));
+
+        let map = test_map();
+        let lazy = LazySourceMapConsumer::new(&map).unwrap();
+        let content = lazy.source_content_for("one.js").unwrap();
+        assert_eq!(content.as_ptr(), map.sources_content[0].as_ref().unwrap().as_ptr());
+    }
+
+    fn test_map() -> SrcMap {
+        SrcMap {
+            version: 3,
+            file: Some(String::from("min.js")),
+            source_root: Some(String::from("/the/root")),
+            sources: vec![String::from("one.js"), String::from("two.js")],
+            sources_content: vec![
+                Some(String::from(" ONE.foo = function (bar) {\n   return baz(bar);\n };")),
+                Some(String::from(" TWO.inc = function (n) {\n   return n + 1;\n };")),
+            ],
+            names: vec![String::from("bar"), String::from("baz"), String::from("n")],
+            mappings: String::from(
+                "CAAC,IAAI,IAAM,SAAUA,GAClB,OAAOC,IAAID;CCDb,IAAI,IAAM,SAAUE,GAClB,OAAOA;;CDAA,GAAG,IAAIA,KAAK,E",
+            ),
+            ..SrcMap::default()
+        }
+    }
+}
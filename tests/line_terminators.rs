@@ -0,0 +1,71 @@
+extern crate source_map;
+
+#[cfg(test)]
+mod line_terminators {
+    use source_map::*;
+
+    fn chunk(position: (usize, usize), code: &str) -> Node {
+        Node::NSourceNode(SourceNode::new(
+            Some(position),
+            Some(StringPtr::Str(String::from("a.js"))),
+            None,
+            Some(Node::NString(String::from(code))),
+        ))
+    }
+
+    fn mappings(chunks: Vec<Node>, line_terminators: LineTerminators) -> String {
+        let mut node = SourceNode::new(None, None, None, None);
+        for chunk in chunks {
+            node.add(chunk);
+        }
+        node.to_string_with_source_map_in(None, None, ColumnUnit::Utf16, line_terminators)
+            .unwrap()
+            .map
+            .mappings
+    }
+
+    #[test]
+    fn split_lines() {
+        let code = "a\r\nb\rc\u{2028}d\u{c}e\n";
+        assert_eq!(
+            LineTerminators::EcmaScript.split_lines(code),
+            vec!["a\r\n", "b\r", "c\u{2028}", "d\u{c}e\n", ""]
+        );
+        assert_eq!(
+            LineTerminators::Css.split_lines(code),
+            vec!["a\r\n", "b\r", "c\u{2028}d\u{c}", "e\n", ""]
+        );
+        assert_eq!(LineTerminators::Lf.split_lines(code), vec!["a\r\n", "b\rc\u{2028}d\u{c}e\n", ""]);
+        assert_eq!(LineTerminators::EcmaScript.trim_terminator("a\r\n"), "a");
+    }
+
+    #[test]
+    fn generation() {
+        let chunks = || vec![chunk((1, 0), "a\u{2028}"), chunk((2, 3), "b")];
+        assert_eq!(mappings(chunks(), LineTerminators::EcmaScript), "AAAA;AACG");
+        assert_eq!(mappings(chunks(), LineTerminators::Lf), "AAAA,EACG");
+    }
+
+    #[test]
+    fn crlf_split_across_chunks() {
+        let chunks = vec![chunk((1, 0), "a\r"), chunk((2, 0), "\nb"), chunk((3, 0), "c")];
+        assert_eq!(mappings(chunks, LineTerminators::EcmaScript), "AAAA;AACA,CACA");
+    }
+
+    #[test]
+    fn from_string_with_crlf() {
+        let consumer = SourceMapConsumer::new(SrcMap {
+            version: 3,
+            sources: vec![String::from("a.js")],
+            mappings: String::from("AAAA;AACA"),
+            ..SrcMap::default()
+        })
+        .unwrap();
+        let node = SourceNode::from_string_with_source_map("a\r\nb", &consumer, None);
+        let mut chunks = Vec::new();
+        node.walk(|chunk, _, position, _| chunks.push((String::from(chunk), position)));
+        assert_eq!(chunks[0], (String::from("a\r\n"), Some((1, 0))));
+        assert_eq!(chunks[1], (String::from("b"), Some((2, 0))));
+        assert_eq!(node.to_string(), "a\r\nb");
+    }
+}
@@ -0,0 +1,145 @@
+extern crate source_map;
+
+#[cfg(test)]
+mod magic_string {
+    use source_map::*;
+
+    #[test]
+    fn edits() {
+        let mut s = MagicString::new("import a from 'a';\n@dec\nclass B {}\n");
+        s.overwrite(7, 8, "alpha").unwrap();
+        s.remove(19, 24).unwrap();
+        s.prepend("/* banner */\n");
+        s.append_left(18, " // a").unwrap();
+        s.prepend_right(24, "export ").unwrap();
+        s.append("B;\n");
+        assert_eq!(
+            s.to_string(),
+            "/* banner */\nimport alpha from 'a'; // a\nexport class B {}\nB;\n"
+        );
+        assert_eq!(s.original(), "import a from 'a';\n@dec\nclass B {}\n");
+    }
+
+    #[test]
+    fn left_and_right_follow_their_side() {
+        let mut s = MagicString::new("abc");
+        s.append_left(1, "L").unwrap();
+        s.prepend_right(1, "R").unwrap();
+        s.prepend_left(1, "l").unwrap();
+        s.append_right(1, "r").unwrap();
+        assert_eq!(s.to_string(), "alLRrbc");
+        // removing a side drops what was attached to it
+        s.remove(0, 1).unwrap();
+        assert_eq!(s.to_string(), "Rrbc");
+        s.remove(1, 3).unwrap();
+        assert_eq!(s.to_string(), "");
+    }
+
+    #[test]
+    fn move_to() {
+        let mut s = MagicString::new("abcdef");
+        s.move_to(0, 2, 6).unwrap();
+        assert_eq!(s.to_string(), "cdefab");
+        s.move_to(4, 6, 2).unwrap();
+        assert_eq!(s.to_string(), "efcdab");
+        match s.move_to(1, 4, 2) {
+            Err(Error::InvalidRange(1, 4)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn char_indices() {
+        let mut s = MagicString::new("\u{1F600}ab");
+        match s.remove(1, 4) {
+            Err(Error::InvalidRange(1, 4)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        s.set_index_unit(ColumnUnit::Char);
+        s.overwrite(1, 2, "x").unwrap();
+        assert_eq!(s.to_string(), "\u{1F600}xb");
+        s.set_index_unit(ColumnUnit::Utf16);
+        s.remove(0, 2).unwrap();
+        assert_eq!(s.to_string(), "xb");
+    }
+
+    #[test]
+    fn cannot_split_edited_range() {
+        let mut s = MagicString::new("abcdef");
+        s.overwrite(1, 4, "x").unwrap();
+        match s.append_left(2, "y") {
+            Err(Error::EditedRange(2)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn overwrite_drops_text_inserted_in_range() {
+        let mut s = MagicString::new("abcdef");
+        s.append_left(4, "X").unwrap();
+        s.overwrite(1, 4, "y").unwrap();
+        assert_eq!(s.to_string(), "ayef");
+
+        let mut s = MagicString::new("abcdef");
+        s.append_left(4, "X").unwrap();
+        s.prepend_right(2, "").unwrap();
+        s.overwrite(1, 4, "y").unwrap();
+        assert_eq!(s.to_string(), "ayef");
+    }
+
+    #[test]
+    fn failed_edits_leave_chunks_alone() {
+        let edited = || {
+            let mut s = MagicString::new("abcdef");
+            s.overwrite(3, 5, "x").unwrap();
+            s
+        };
+        let mut s = edited();
+        for result in [s.overwrite(1, 4, "y"), s.remove(1, 4), s.move_to(1, 4, 6)] {
+            match result {
+                Err(Error::EditedRange(4)) => {}
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        let mappings = |s: &MagicString| s.to_source_map_generator("a.js", None).to_source_map().unwrap().mappings;
+        assert_eq!(mappings(&s), mappings(&edited()));
+    }
+
+    #[test]
+    fn move_to_needs_contiguous_range() {
+        let mut s = MagicString::new("abcdef");
+        s.move_to(2, 3, 0).unwrap();
+        assert_eq!(s.to_string(), "cabdef");
+        match s.move_to(1, 4, 6) {
+            Err(Error::InvalidRange(1, 4)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(s.to_string(), "cabdef");
+    }
+
+    #[test]
+    fn source_map() {
+        let mut s = MagicString::new("let a = 1;\nlet b = 2;\n");
+        s.prepend("'use strict';\n");
+        s.overwrite(4, 5, "alpha").unwrap();
+        s.remove(11, 22).unwrap();
+        s.append("let c = 3;\n");
+        let result = s.to_string_with_source_map("input.js", None).unwrap();
+        assert_eq!(result.source, "'use strict';\nlet alpha = 1;\nlet c = 3;\n");
+        assert_eq!(result.map.sources, vec!["input.js"]);
+        assert_eq!(
+            result.map.sources_content,
+            vec![Some(String::from("let a = 1;\nlet b = 2;\n"))]
+        );
+
+        let consumer = SourceMapConsumer::new(result.map).unwrap();
+        let mapping = consumer.original_position_for(2, 0, Bias::GreatestLowerBound);
+        assert_eq!(mapping.original, Some((1, 0)));
+        let mapping = consumer.original_position_for(2, 6, Bias::GreatestLowerBound);
+        assert_eq!(mapping.original, Some((1, 4)));
+        let mapping = consumer.original_position_for(2, 10, Bias::GreatestLowerBound);
+        assert_eq!(mapping.original, Some((1, 5)));
+        let mapping = consumer.original_position_for(3, 0, Bias::GreatestLowerBound);
+        assert_eq!(mapping.original, None);
+    }
+}
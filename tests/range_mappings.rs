@@ -0,0 +1,144 @@
+extern crate source_map;
+
+#[cfg(test)]
+mod range_mappings {
+    use source_map::*;
+
+    fn generator() -> SourceMapGenerator {
+        let mut generator = SourceMapGenerator::new(None, None, false);
+        for &(generated, original, range) in &[
+            ((1, 0), (1, 0), true),
+            ((1, 10), (5, 0), false),
+            ((3, 0), (1, 0), false),
+            ((3, 4), (2, 2), true),
+        ] {
+            generator.add_mapping(Mapping {
+                generated,
+                source: Some(Ptr::new(String::from("a.js"))),
+                name: None,
+                original: Some(original),
+                range,
+            }).unwrap();
+        }
+        generator
+    }
+
+    #[test]
+    fn serializes_range_mappings() {
+        let map = generator().to_source_map().unwrap();
+        assert_eq!(map.range_mappings, Some(String::from("B;;C")));
+
+        let json = map.to_json().unwrap();
+        assert!(json.contains(r#","rangeMappings":"B;;C""#));
+        let mut out = Vec::new();
+        generator().write_source_map(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), json);
+        assert_eq!(from_source_map(&json, false).unwrap().to_source_map().unwrap(), map);
+
+        let mut plain = SourceMapGenerator::new(None, None, false);
+        plain.add_mapping(Mapping {
+            generated: (1, 0),
+            source: Some(Ptr::new(String::from("a.js"))),
+            name: None,
+            original: Some((1, 0)),
+            range: false,
+        }).unwrap();
+        assert_eq!(plain.to_source_map().unwrap().range_mappings, None);
+    }
+
+    #[test]
+    fn lookups_interpolate_inside_ranges() {
+        let map = generator().to_source_map().unwrap();
+        let consumer = SourceMapConsumer::new(map.clone()).unwrap();
+        let mut lazy = LazySourceMapConsumer::new(map).unwrap();
+
+        for &(line, column, bias, expected) in &[
+            (1, 7, Bias::LeastUpperBound, Some((1, 7))),
+            (1, 10, Bias::GreatestLowerBound, Some((5, 0))),
+            (1, 12, Bias::GreatestLowerBound, Some((5, 0))),
+            (3, 9, Bias::GreatestLowerBound, Some((2, 7))),
+            (3, 2, Bias::GreatestLowerBound, Some((1, 0))),
+        ] {
+            let found = consumer.original_position_for(line, column, bias);
+            assert_eq!(found.original, expected);
+            assert_eq!(lazy.original_position_for(line, column, bias).unwrap().original, expected);
+        }
+        assert_eq!(consumer.original_position_for(1, 7, Bias::GreatestLowerBound).generated, (1, 7));
+
+        let at = |line, column| consumer.generated_position_for("a.js", line, column, Bias::GreatestLowerBound).generated;
+        assert_eq!(at(1, 7), (1, 7));
+        // past the end of the range, which stops at the next mapping
+        assert_eq!(at(1, 12), (1, 0));
+        assert_eq!(at(2, 5), (3, 7));
+    }
+
+    #[test]
+    fn multi_digit_indices() {
+        let mut mappings = String::from("AAAA");
+        for _ in 0..40 {
+            mappings += ",CAAC";
+        }
+        let map = SrcMap {
+            sources: vec![String::from("a.js")],
+            mappings,
+            range_mappings: Some(String::from("pB")),
+            ..SrcMap::default()
+        };
+        let consumer = SourceMapConsumer::new(map).unwrap();
+        let found = consumer.original_position_for(1, 45, Bias::GreatestLowerBound);
+        assert_eq!(found.original, Some((1, 45)));
+        assert_eq!(consumer.original_position_for(1, 39, Bias::GreatestLowerBound).original, Some((1, 39)));
+    }
+
+    #[test]
+    fn indices_follow_encoded_order() {
+        // the segment at column 0 is encoded second, and it is the range mapping
+        let map = SrcMap {
+            sources: vec![String::from("a.js")],
+            mappings: String::from("EAAE,FAAF"),
+            range_mappings: Some(String::from("C")),
+            ..SrcMap::default()
+        };
+        let consumer = SourceMapConsumer::new(map.clone()).unwrap();
+        let mut lazy = LazySourceMapConsumer::new(map.clone()).unwrap();
+        let mut generated = from_source_map(&map.to_json().unwrap(), false).unwrap();
+        let regenerated = SourceMapConsumer::new(generated.to_source_map().unwrap()).unwrap();
+        for &(column, expected) in &[(1, (1, 1)), (3, (1, 2))] {
+            assert_eq!(consumer.original_position_for(1, column, Bias::GreatestLowerBound).original, Some(expected));
+            assert_eq!(lazy.original_position_for(1, column, Bias::GreatestLowerBound).unwrap().original, Some(expected));
+            assert_eq!(regenerated.original_position_for(1, column, Bias::GreatestLowerBound).original, Some(expected));
+        }
+    }
+
+    #[test]
+    fn rejects_ranges_without_source() {
+        let mut generator = SourceMapGenerator::new(None, None, false);
+        let mapping = Mapping {
+            generated: (1, 0),
+            source: None,
+            name: None,
+            original: None,
+            range: true,
+        };
+        match generator.add_mapping(mapping.clone()) {
+            Err(Error::InvalidMapping(found)) => assert_eq!(found, mapping),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_range_mappings() {
+        for &(range_mappings, offset) in &[("B;!", 2), ("A", 0), ("Bg", 1)] {
+            let map = SrcMap {
+                sources: vec![String::from("a.js")],
+                mappings: String::from("AAAA"),
+                range_mappings: Some(String::from(range_mappings)),
+                ..SrcMap::default()
+            };
+            match SourceMapConsumer::new(map) {
+                Err(Error::InvalidRangeMappings(found)) => assert_eq!(found, offset),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+extern crate source_map;
+
+#[cfg(test)]
+mod remap {
+    use source_map::*;
+
+    type MappingParams<'a> = ((usize, usize), &'a str, (usize, usize), Option<&'a str>);
+
+    fn build(file: &str, mappings: &[MappingParams], content: Option<(&str, &str)>) -> SrcMap {
+        let mut generator = SourceMapGenerator::new(Some(StringPtr::Str(String::from(file))), None, false);
+        for &(generated, source, original, name) in mappings {
+            generator.add_mapping(Mapping {
+                generated,
+                source: Some(Ptr::new(String::from(source))),
+                name: name.map(|s| Ptr::new(String::from(s))),
+                original: Some(original),
+                range: false,
+            }).unwrap();
+        }
+        if let Some((source, content)) = content {
+            generator.set_source_content(
+                StringPtr::Str(String::from(source)),
+                Some(StringPtr::Str(String::from(content))),
+            );
+        }
+        generator.to_source_map().unwrap()
+    }
+
+    fn loader(source: &str) -> Option<SrcMap> {
+        match source {
+            "src/a.js" => Some(build(
+                "a.js",
+                &[
+                    ((1, 0), "b.js", (1, 0), None),
+                    ((1, 4), "b.js", (2, 0), Some("mid")),
+                    ((1, 8), "b.js", (3, 0), None),
+                    ((2, 0), "other.js", (1, 0), None),
+                ],
+                None,
+            )),
+            "src/b.js" => Some(build(
+                "b.js",
+                &[((1, 0), "c.ts", (1, 0), Some("orig")), ((2, 0), "c.ts", (2, 4), None)],
+                Some(("c.ts", "let c;\n    c = 1;")),
+            )),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn flattens_chain() {
+        let map = build(
+            "min.js",
+            &[
+                ((1, 0), "src/a.js", (1, 0), None),
+                ((1, 10), "src/a.js", (1, 4), None),
+                ((1, 20), "src/a.js", (1, 8), None),
+                ((2, 0), "src/a.js", (2, 0), None),
+            ],
+            None,
+        );
+        let mut generator = remap(map, loader).unwrap();
+        let map = generator.to_source_map().unwrap();
+        assert_eq!(map.file, Some(String::from("min.js")));
+        assert_eq!(map.sources, ["src/c.ts", "src/other.js"]);
+        assert_eq!(map.names, ["orig", "mid"]);
+        assert_eq!(map.sources_content, [Some(String::from("let c;\n    c = 1;")), None]);
+
+        let consumer = SourceMapConsumer::new(map).unwrap();
+        let mut mappings = Vec::new();
+        consumer.each_mapping(MappingOrder::Generated, |mapping| {
+            mappings.push((
+                mapping.generated,
+                mapping.source.as_ref().map(|s| s.to_string()),
+                mapping.original,
+                mapping.name.as_ref().map(|s| s.to_string()),
+            ))
+        });
+        let string = |s: &str| Some(String::from(s));
+        assert_eq!(
+            mappings,
+            [
+                ((1, 0), string("src/c.ts"), Some((1, 0)), string("orig")),
+                ((1, 10), string("src/c.ts"), Some((2, 4)), string("mid")),
+                ((2, 0), string("src/other.js"), Some((1, 0)), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_on_cycles() {
+        let cyclic = |source: &str| Some(build(source, &[((1, 0), source, (1, 0), None)], None));
+        let map = build("min.js", &[((1, 0), "a.js", (1, 2), None)], None);
+        let mut generator = remap(map, cyclic).unwrap();
+        let map = generator.to_source_map().unwrap();
+        assert_eq!(map.sources, ["a.js"]);
+        assert_eq!(map.mappings, "AAAA");
+    }
+
+    #[test]
+    fn keeps_ranges_of_leaf_sources() {
+        let mut generator = SourceMapGenerator::new(None, None, false);
+        for &(generated, source) in &[((1, 0), "leaf.js"), ((1, 10), "src/b.js")] {
+            generator.add_mapping(Mapping {
+                generated,
+                source: Some(Ptr::new(String::from(source))),
+                name: None,
+                original: Some((1, 0)),
+                range: true,
+            }).unwrap();
+        }
+        let map = generator.to_source_map().unwrap();
+        let mut generator = remap(map, loader).unwrap();
+        let consumer = SourceMapConsumer::new(generator.to_source_map().unwrap()).unwrap();
+        let mut ranges = Vec::new();
+        consumer.each_mapping(MappingOrder::Generated, |mapping| ranges.push((mapping.generated, mapping.range)));
+        assert_eq!(ranges, [((1, 0), true), ((1, 10), false)]);
+    }
+}
@@ -0,0 +1,210 @@
+extern crate source_map;
+
+#[cfg(test)]
+mod scopes {
+    use source_map::*;
+
+    fn strings(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| String::from(*s)).collect()
+    }
+
+    fn scope(start: (usize, usize), end: (usize, usize), name: Option<&str>, kind: &str, variables: &[&str]) -> OriginalScope {
+        OriginalScope {
+            start,
+            end,
+            name: name.map(String::from),
+            kind: Some(String::from(kind)),
+            is_stack_frame: name.is_some(),
+            variables: strings(variables),
+            children: Vec::new(),
+        }
+    }
+
+    fn range(start: (usize, usize), end: (usize, usize), index: usize, bindings: &[Option<&str>]) -> GeneratedRange {
+        GeneratedRange {
+            start,
+            end,
+            definition: Some(ScopeDefinition {
+                source: String::from("a.js"),
+                index,
+            }),
+            call_site: None,
+            is_stack_frame: index > 0,
+            is_hidden: false,
+            bindings: bindings.iter().map(|binding| binding.map(String::from)).collect(),
+            children: Vec::new(),
+        }
+    }
+
+    fn original_scopes() -> Vec<OriginalScope> {
+        let mut global = scope((1, 0), (6, 0), None, "global", &["outer", "inner"]);
+        global.children.push(scope((1, 14), (4, 1), Some("outer"), "function", &["x", "y"]));
+        global.children.push(scope((5, 14), (5, 31), Some("inner"), "function", &["z"]));
+        vec![global]
+    }
+
+    fn generated_ranges() -> GeneratedRange {
+        let mut inlined = range((1, 22), (1, 30), 2, &[Some("b")]);
+        inlined.call_site = Some(CallSite {
+            source: String::from("a.js"),
+            line: 3,
+            column: 2,
+        });
+        let mut outer = range((1, 10), (1, 31), 1, &[Some("a"), Some("b")]);
+        outer.children.push(inlined);
+        let mut global = range((1, 0), (2, 0), 0, &[Some("o"), None]);
+        global.children.push(outer);
+        global
+    }
+
+    fn generator() -> SourceMapGenerator {
+        let mut generator = SourceMapGenerator::new(Some(StringPtr::Str(String::from("a.min.js"))), None, false);
+        generator.add_mapping(Mapping {
+            generated: (1, 0),
+            source: Some(Ptr::new(String::from("a.js"))),
+            name: None,
+            original: Some((1, 0)),
+            range: false,
+        }).unwrap();
+        generator.set_original_scopes(StringPtr::Str(String::from("a.js")), original_scopes());
+        generator.add_generated_range(generated_ranges());
+        generator
+    }
+
+    #[test]
+    fn round_trips() {
+        let map = generator().to_source_map().unwrap();
+        assert_eq!(map.original_scopes.len(), 1);
+        assert!(map.generated_ranges.is_some());
+        assert!(map.names.contains(&String::from("outer")));
+
+        let json = map.to_json().unwrap();
+        let mut out = Vec::new();
+        generator().write_source_map(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), json);
+        assert_eq!(from_source_map(&json, false).unwrap().to_source_map().unwrap(), map);
+
+        let consumer = SourceMapConsumer::from_json(&json).unwrap();
+        assert_eq!(consumer.original_scopes("a.js"), &original_scopes()[..]);
+        assert_eq!(consumer.generated_ranges(), &[generated_ranges()][..]);
+        assert!(consumer.original_scopes("b.js").is_empty());
+    }
+
+    // the inlining example of the scopes proposal, with `z` inlined into its call:
+    //   var x = 1;
+    //   function z(message) {
+    //     let y = 2;
+    //     console.log(message + y);
+    //   }
+    //   z("Hello World");
+    // generated as
+    //   var x=1;function z(n){let y=2;console.log(n+y)}console.log("Hello World"+2);
+    #[test]
+    fn decodes_fixed_encoding() {
+        let json = r#"{"version":3,"sources":["a.js"],"names":["global","x","z","function","message","y","n"],"mappings":"AAAA","originalScopes":["AAEACC,CUOACCC,GC,CiB"],"generatedRanges":"ACAAEG,kBKACOM,6B,YOAAAKAAA,e,E"}"#;
+        let consumer = SourceMapConsumer::from_json(json).unwrap();
+
+        let mut global = scope((1, 0), (6, 17), None, "global", &["x", "z"]);
+        global.children.push(scope((2, 10), (5, 1), Some("z"), "function", &["message", "y"]));
+        assert_eq!(consumer.original_scopes("a.js"), &[global][..]);
+
+        let mut inlined = range((1, 59), (1, 74), 1, &[None, None]);
+        inlined.call_site = Some(CallSite {
+            source: String::from("a.js"),
+            line: 6,
+            column: 0,
+        });
+        let mut global = range((1, 0), (1, 76), 0, &[Some("x"), Some("z")]);
+        global.children.push(range((1, 18), (1, 47), 1, &[Some("n"), Some("y")]));
+        global.children.push(inlined);
+        assert_eq!(consumer.generated_ranges(), &[global][..]);
+
+        let map = from_source_map(json, false).unwrap().to_source_map().unwrap();
+        assert_eq!(map.original_scopes, ["AAEACC,CUOACCC,GC,CiB"]);
+        assert_eq!(map.generated_ranges.as_ref().unwrap(), "ACAAEG,kBKACOM,6B,YOAAAKAAA,e,E");
+        assert_eq!(map.to_json().unwrap(), json);
+    }
+
+    #[test]
+    fn scope_chain_at_generated_position() {
+        let consumer = SourceMapConsumer::new(generator().to_source_map().unwrap()).unwrap();
+
+        let chain = consumer.scopes_at(1, 25);
+        let names: Vec<Vec<Option<&str>>> = chain
+            .iter()
+            .map(|(_, scopes)| scopes.iter().map(|scope| scope.name.as_deref()).collect())
+            .collect();
+        assert_eq!(names, [vec![Some("inner"), None], vec![Some("outer"), None], vec![None]]);
+        let (inlined, _) = chain[0];
+        assert_eq!(inlined.call_site.as_ref().map(|call_site| call_site.line), Some(3));
+        assert_eq!(inlined.bindings, [Some(String::from("b"))]);
+
+        assert_eq!(consumer.scopes_at(1, 5).len(), 1);
+        assert_eq!(consumer.scopes_at(1, 30).len(), 2);
+        assert!(consumer.scopes_at(2, 0).is_empty());
+    }
+
+    #[test]
+    fn enclosing_scopes_without_ranges() {
+        let mut generator = generator();
+        generator.add_generated_range(range((3, 0), (4, 0), 1, &[None, None]));
+        let consumer = SourceMapConsumer::new(generator.to_source_map().unwrap()).unwrap();
+        let chain = consumer.scopes_at(3, 5);
+        assert_eq!(chain.len(), 1);
+        let names: Vec<Option<&str>> = chain[0].1.iter().map(|scope| scope.name.as_deref()).collect();
+        assert_eq!(names, [Some("outer"), None]);
+    }
+
+    #[test]
+    fn scopes_are_keyed_relative_to_the_root() {
+        let mut generator = SourceMapGenerator::new(None, Some(StringPtr::Str(String::from("/the/root"))), false);
+        generator.add_mapping(Mapping {
+            generated: (1, 0),
+            source: Some(Ptr::new(String::from("a.js"))),
+            name: None,
+            original: Some((1, 0)),
+            range: false,
+        }).unwrap();
+        generator.set_original_scopes(StringPtr::Str(String::from("/the/root/a.js")), original_scopes());
+        let map = generator.to_source_map().unwrap();
+        assert_eq!(map.sources, ["a.js"]);
+        assert_eq!(map.original_scopes.len(), 1);
+
+        let consumer = SourceMapConsumer::new(map).unwrap();
+        assert_eq!(consumer.original_scopes("a.js"), &original_scopes()[..]);
+    }
+
+    #[test]
+    fn rejects_invalid_scopes() {
+        let unclosed = SrcMap {
+            sources: vec![String::from("a.js")],
+            mappings: String::from("AAAA"),
+            original_scopes: vec![String::from("AAA")],
+            ..SrcMap::default()
+        };
+        match SourceMapConsumer::new(unclosed) {
+            Err(Error::InvalidScopes(3)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let unknown_source = SrcMap {
+            sources: vec![String::from("a.js")],
+            mappings: String::from("AAAA"),
+            generated_ranges: Some(String::from("ACCA,E")),
+            ..SrcMap::default()
+        };
+        match SourceMapConsumer::new(unknown_source) {
+            Err(Error::SourceOutOfRange(1)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let mut generator = generator();
+        let mut range = generated_ranges();
+        range.definition = Some(ScopeDefinition {
+            source: String::from("missing.js"),
+            index: 0,
+        });
+        generator.add_generated_range(range);
+        assert!(generator.to_source_map().is_err());
+    }
+}
@@ -0,0 +1,143 @@
+extern crate serde_json;
+extern crate source_map;
+
+#[cfg(test)]
+mod src_map_json {
+    use serde_json;
+    use source_map::*;
+
+    #[test]
+    fn round_trips_spec_field_names() {
+        let json = concat!(
+            r#"{"version":3,"file":"min.js","sourceRoot":"/the/root","sources":["one.js"],"#,
+            r#""sourcesContent":["ONE.foo = 1;"],"names":["bar"],"mappings":"CAAC,IAAIA"}"#
+        );
+        let map = SrcMap::from_json(json).unwrap();
+        assert_eq!(map.source_root, Some(String::from("/the/root")));
+        assert_eq!(map.sources_content, [Some(String::from("ONE.foo = 1;"))]);
+        assert_eq!(map.to_json().unwrap(), json);
+    }
+
+    #[test]
+    fn omits_absent_optional_fields() {
+        let json = r#"{"version":3,"sources":["a.ts"],"names":[],"mappings":"AAAA"}"#;
+        let map = SrcMap::from_json(json).unwrap();
+        assert_eq!(map.file, None);
+        assert_eq!(map.source_root, None);
+        assert_eq!(map.to_json().unwrap(), json);
+
+        let minimal = SrcMap::from_json(r#"{"version":3,"sources":[],"mappings":""}"#).unwrap();
+        assert!(minimal.names.is_empty());
+        assert!(minimal.sources_content.is_empty());
+    }
+
+    #[test]
+    fn null_sources_content_entries() {
+        let json = r#"{"version":3,"sources":["a.js","b.js"],"sourcesContent":[null,"b"],"names":[],"mappings":"AAAA,CCAA"}"#;
+        let map = SrcMap::from_json(json).unwrap();
+        assert_eq!(map.sources_content, [None, Some(String::from("b"))]);
+        assert_eq!(map.to_json().unwrap(), json);
+
+        let mut generator = from_source_map(json, false).unwrap();
+        assert_eq!(generator.to_source_map().unwrap(), map);
+    }
+
+    #[test]
+    fn ignore_list_fields() {
+        let json = r#"{"version":3,"sources":["a.js","node_modules/b.js"],"names":[],"mappings":"AAAA,CCAA","ignoreList":[1]}"#;
+        let map = SrcMap::from_json(json).unwrap();
+        assert_eq!(map.ignored_indices(), [1]);
+        assert_eq!(map.to_json().unwrap(), json);
+
+        let legacy = r#"{"version":3,"sources":["a.js","node_modules/b.js"],"names":[],"mappings":"AAAA,CCAA","x_google_ignoreList":[1]}"#;
+        let map = SrcMap::from_json(legacy).unwrap();
+        assert_eq!(map.ignored_indices(), [1]);
+        assert_eq!(map.to_json().unwrap(), legacy);
+        let consumer = SourceMapConsumer::new(map).unwrap();
+        assert!(consumer.is_ignored("node_modules/b.js"));
+        assert!(!consumer.is_ignored("a.js"));
+
+        let mut generator = from_source_map(legacy, false).unwrap();
+        assert_eq!(generator.to_source_map().unwrap().ignore_list, [1]);
+    }
+
+    #[test]
+    fn rejects_other_versions_unless_lenient() {
+        let json = r#"{"version":2,"sources":[],"names":[],"mappings":""}"#;
+        match SrcMap::from_json(json) {
+            Err(Error::UnsupportedVersion(2)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(SrcMap::from_json_lenient(json).unwrap().version, 2);
+
+        let index = r#"{"version":3,"sections":[{"offset":{"line":0,"column":0},"map":{"version":4,"sources":[],"mappings":""}}]}"#;
+        match IndexSrcMap::from_json(index) {
+            Err(Error::UnsupportedVersion(4)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(IndexSrcMap::from_json_lenient(index).is_ok());
+    }
+
+    #[test]
+    fn string_with_source_map_shape() {
+        let node = SourceNode::new(
+            Some((1, 0)),
+            Some(StringPtr::Str(String::from("a.js"))),
+            None,
+            Some(Node::NString(String::from("a;"))),
+        );
+        let result = node.to_string_with_source_map(None, None).unwrap();
+        let json = serde_json::to_string(&result).unwrap();
+        assert_eq!(
+            json,
+            r#"{"source":"a;","map":{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA"}}"#
+        );
+        assert_eq!(serde_json::from_str::<StringWithSrcMap>(&json).unwrap(), result);
+    }
+}
+
+#[cfg(test)]
+mod src_map_ref {
+    use source_map::*;
+    use std::borrow::Cow;
+
+    #[test]
+    fn borrows_from_input() {
+        let json = concat!(
+            r#"{"version":3,"file":"min.js","sources":["a.js","b.js"],"#,
+            r#""sourcesContent":["var a = 1;",null],"names":["a"],"mappings":"AAAAA,CCAA"}"#
+        );
+        let map = SrcMapRef::from_json(json).unwrap();
+        match map.sources_content[0] {
+            Some(Cow::Borrowed(content)) => assert_eq!(content, "var a = 1;"),
+            ref other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(map.sources_content[1], None);
+        assert!(match map.mappings {
+            Cow::Borrowed(_) => true,
+            Cow::Owned(_) => false,
+        });
+        assert!(matches!(map.file, Some(Cow::Borrowed("min.js"))));
+        assert!(map.sources.iter().all(|source| matches!(source, Cow::Borrowed(_))));
+        assert!(matches!(map.names[0], Cow::Borrowed("a")));
+        assert_eq!(map.to_json().unwrap(), json);
+
+        let owned = map.into_owned();
+        assert_eq!(owned, SrcMap::from_json(json).unwrap());
+        assert_eq!(SrcMapRef::from(&owned).into_owned(), owned);
+    }
+
+    #[test]
+    fn unescapes_into_owned_strings() {
+        let json = r#"{"version":3,"sources":["a\"b.js"],"sourcesContent":["line\nline"],"mappings":"AAAA"}"#;
+        let map = SrcMapRef::from_json(json).unwrap();
+        assert_eq!(map.sources, ["a\"b.js"]);
+        assert_eq!(map.sources_content, [Some(Cow::from("line\nline"))]);
+        assert!(map.names.is_empty());
+
+        match SrcMapRef::from_json(r#"{"version":2,"sources":[],"mappings":""}"#) {
+            Err(Error::UnsupportedVersion(2)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
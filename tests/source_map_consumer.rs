@@ -0,0 +1,176 @@
+extern crate source_map;
+
+#[cfg(test)]
+mod source_map_consumer {
+    use source_map::*;
+
+    #[test]
+    fn mapping_tokens_back_exactly() {
+        let map = SourceMapConsumer::new(test_map()).unwrap();
+        assert_mapping(&map, 1, 1, "/the/root/one.js", 1, 1, None);
+        assert_mapping(&map, 1, 5, "/the/root/one.js", 1, 5, None);
+        assert_mapping(&map, 1, 9, "/the/root/one.js", 1, 11, None);
+        assert_mapping(&map, 1, 18, "/the/root/one.js", 1, 21, Some("bar"));
+        assert_mapping(&map, 1, 21, "/the/root/one.js", 2, 3, None);
+        assert_mapping(&map, 1, 28, "/the/root/one.js", 2, 10, Some("baz"));
+        assert_mapping(&map, 1, 32, "/the/root/one.js", 2, 14, Some("bar"));
+
+        assert_mapping(&map, 2, 1, "/the/root/two.js", 1, 1, None);
+        assert_mapping(&map, 2, 5, "/the/root/two.js", 1, 5, None);
+        assert_mapping(&map, 2, 9, "/the/root/two.js", 1, 11, None);
+        assert_mapping(&map, 2, 18, "/the/root/two.js", 1, 21, Some("n"));
+        assert_mapping(&map, 2, 21, "/the/root/two.js", 2, 3, None);
+        assert_mapping(&map, 2, 28, "/the/root/two.js", 2, 10, Some("n"));
+    }
+
+    #[test]
+    fn original_position_for_with_bias() {
+        let map = SourceMapConsumer::new(test_map()).unwrap();
+
+        let glb = map.original_position_for(1, 20, Bias::GreatestLowerBound);
+        assert_eq!(glb.original, Some((1, 21)));
+        assert_eq!(glb.name.map(|s| (*s).clone()), Some(String::from("bar")));
+
+        let lub = map.original_position_for(1, 20, Bias::LeastUpperBound);
+        assert_eq!(lub.original, Some((2, 3)));
+
+        let missing = map.original_position_for(1, 0, Bias::GreatestLowerBound);
+        assert_eq!(missing.source, None);
+        assert_eq!(missing.original, None);
+
+        let other_line = map.original_position_for(1, 40, Bias::LeastUpperBound);
+        assert_eq!(other_line.source, None);
+    }
+
+    #[test]
+    fn sources_and_contents() {
+        let map = SourceMapConsumer::new(test_map()).unwrap();
+        let sources: Vec<&str> = map.sources().iter().map(|s| s.as_str()).collect();
+        assert_eq!(sources, ["/the/root/one.js", "/the/root/two.js"]);
+        assert!(map.has_contents_of_all_sources());
+        assert_eq!(
+            map.source_content_for("one.js").map(|s| (*s).clone()),
+            Some(String::from(" ONE.foo = function (bar) {\n   return baz(bar);\n };"))
+        );
+        assert_eq!(
+            map.source_content_for("/the/root/two.js").map(|s| (*s).clone()),
+            Some(String::from(" TWO.inc = function (n) {\n   return n + 1;\n };"))
+        );
+        assert_eq!(map.source_content_for("three.js"), None);
+
+        let mut without_contents = test_map();
+        without_contents.sources_content = Vec::new();
+        assert!(!SourceMapConsumer::new(without_contents).unwrap().has_contents_of_all_sources());
+
+        let mut missing_content = test_map();
+        missing_content.sources_content[0] = None;
+        let missing_content = SourceMapConsumer::new(missing_content).unwrap();
+        assert!(!missing_content.has_contents_of_all_sources());
+        assert_eq!(missing_content.source_content_for("one.js"), None);
+        assert!(missing_content.source_content_for("two.js").is_some());
+    }
+
+    #[test]
+    fn each_mapping_in_order() {
+        let map = SourceMapConsumer::new(test_map()).unwrap();
+
+        let mut generated = Vec::new();
+        map.each_mapping(MappingOrder::Generated, |mapping| generated.push(mapping.generated));
+        assert_eq!(generated.len(), 13);
+        assert!(generated.windows(2).all(|w| w[0] < w[1]));
+
+        let mut original = Vec::new();
+        map.each_mapping(MappingOrder::Original, |mapping| {
+            original.push(((*mapping.source.clone().unwrap()).clone(), mapping.original.unwrap()))
+        });
+        assert_eq!(original.len(), 13);
+        assert!(original.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(original[0], (String::from("/the/root/one.js"), (1, 1)));
+    }
+
+    #[test]
+    fn generated_position_for_with_bias() {
+        let map = SourceMapConsumer::new(test_map()).unwrap();
+
+        let exact = map.generated_position_for("one.js", 2, 10, Bias::GreatestLowerBound);
+        assert_eq!(exact.generated, (1, 28));
+        let resolved = map.generated_position_for("/the/root/two.js", 1, 21, Bias::GreatestLowerBound);
+        assert_eq!(resolved.generated, (2, 18));
+
+        let glb = map.generated_position_for("one.js", 2, 12, Bias::GreatestLowerBound);
+        assert_eq!(glb.generated, (1, 28));
+        let lub = map.generated_position_for("one.js", 2, 12, Bias::LeastUpperBound);
+        assert_eq!(lub.generated, (1, 32));
+
+        let other_source = map.generated_position_for("two.js", 1, 0, Bias::GreatestLowerBound);
+        assert_eq!(other_source.source, None);
+        let missing = map.generated_position_for("three.js", 1, 1, Bias::GreatestLowerBound);
+        assert_eq!(missing.source, None);
+    }
+
+    #[test]
+    fn all_generated_positions_for() {
+        let mut generator = SourceMapGenerator::new(None, None, false);
+        for &(generated, source, original) in &[
+            ((1, 2), "foo.coffee", (1, 1)),
+            ((1, 2), "bar.coffee", (1, 1)),
+            ((2, 2), "bar.coffee", (2, 1)),
+            ((2, 3), "bar.coffee", (2, 2)),
+            ((3, 2), "bar.coffee", (4, 1)),
+            ((4, 2), "bar.coffee", (4, 1)),
+        ] {
+            generator.add_mapping(Mapping {
+                generated,
+                source: Some(Ptr::new(String::from(source))),
+                name: None,
+                original: Some(original),
+                range: false,
+            }).unwrap();
+        }
+        let map = SourceMapConsumer::new(generator.to_source_map().unwrap()).unwrap();
+        let generated = |mappings: Vec<Mapping>| -> Vec<(usize, usize)> {
+            mappings.iter().map(|mapping| mapping.generated).collect()
+        };
+
+        assert_eq!(generated(map.all_generated_positions_for("bar.coffee", 2, None)), [(2, 2), (2, 3)]);
+        assert_eq!(generated(map.all_generated_positions_for("bar.coffee", 4, Some(1))), [(3, 2), (4, 2)]);
+        assert_eq!(generated(map.all_generated_positions_for("bar.coffee", 3, None)), [(3, 2), (4, 2)]);
+        assert!(map.all_generated_positions_for("bar.coffee", 3, Some(1)).is_empty());
+        assert!(map.all_generated_positions_for("foo.coffee", 2, None).is_empty());
+        assert!(map.all_generated_positions_for("baz.coffee", 1, None).is_empty());
+    }
+
+    fn test_map() -> SrcMap {
+        SrcMap {
+            version: 3,
+            file: Some(String::from("min.js")),
+            source_root: Some(String::from("/the/root")),
+            sources: vec![String::from("one.js"), String::from("two.js")],
+            sources_content: vec![
+                Some(String::from(" ONE.foo = function (bar) {\n   return baz(bar);\n };")),
+                Some(String::from(" TWO.inc = function (n) {\n   return n + 1;\n };")),
+            ],
+            names: vec![String::from("bar"), String::from("baz"), String::from("n")],
+            mappings: String::from(
+                "CAAC,IAAI,IAAM,SAAUA,GAClB,OAAOC,IAAID;CCDb,IAAI,IAAM,SAAUE,GAClB,OAAOA",
+            ),
+            ..SrcMap::default()
+        }
+    }
+
+    fn assert_mapping(
+        map: &SourceMapConsumer,
+        generated_line: usize,
+        generated_column: usize,
+        source: &str,
+        original_line: usize,
+        original_column: usize,
+        name: Option<&str>,
+    ) {
+        let mapping =
+            map.original_position_for(generated_line, generated_column, Bias::GreatestLowerBound);
+        assert_eq!(mapping.source.map(|s| (*s).clone()), Some(String::from(source)));
+        assert_eq!(mapping.original, Some((original_line, original_column)));
+        assert_eq!(mapping.name.map(|s| (*s).clone()), name.map(String::from));
+    }
+}
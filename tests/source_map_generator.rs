@@ -0,0 +1,177 @@
+extern crate source_map;
+
+#[cfg(test)]
+mod apply_source_map {
+    use source_map::*;
+
+    #[test]
+    fn rewrites_mappings_through_applied_map() {
+        let mut generator = step2();
+        generator.apply_source_map(&SourceMapConsumer::new(step1()).unwrap(), None, None).unwrap();
+        let map = generator.to_source_map().unwrap();
+
+        assert_eq!(map.sources, ["fileX", "fileY", "fileB", "fileA"]);
+        assert_eq!(map.names, ["x"]);
+        assert_eq!(
+            map.sources_content,
+            [Some(String::from("lineX1\nlineX2\n")), Some(String::from("lineY1\nlineY2\n")), None, None]
+        );
+
+        let consumer = SourceMapConsumer::new(map).unwrap();
+        let at = |line| consumer.original_position_for(line, 0, Bias::GreatestLowerBound);
+        assert_eq!(at(2).source.map(|s| (*s).clone()), Some(String::from("fileX")));
+        assert_eq!(at(2).original, Some((2, 0)));
+        assert_eq!(at(2).name.map(|s| (*s).clone()), Some(String::from("x")));
+        assert_eq!(at(3).source.map(|s| (*s).clone()), Some(String::from("fileY")));
+        assert_eq!(at(4).source.map(|s| (*s).clone()), Some(String::from("fileB")));
+        assert_eq!(at(5).source.map(|s| (*s).clone()), Some(String::from("fileA")));
+        assert_eq!(at(5).original, Some((2, 0)));
+    }
+
+    #[test]
+    fn resolves_sources_relative_to_source_map_path() {
+        let mut generator = step2();
+        generator.apply_source_map(
+            &SourceMapConsumer::new(step1()).unwrap(),
+            Some(StringPtr::Str(String::from("fileA"))),
+            Some(StringPtr::Str(String::from("lib"))),
+        ).unwrap();
+        let map = generator.to_source_map().unwrap();
+        assert_eq!(map.sources, ["lib/fileX", "lib/fileY", "fileB", "fileA"]);
+
+        let mut generator = step2();
+        generator.apply_source_map(
+            &SourceMapConsumer::new(step1()).unwrap(),
+            Some(StringPtr::Str(String::from("fileB"))),
+            None,
+        ).unwrap();
+        let map = generator.to_source_map().unwrap();
+        assert_eq!(map.sources, ["fileA", "fileX"]);
+    }
+
+    fn step1() -> SrcMap {
+        let mut generator = SourceMapGenerator::new(Some(StringPtr::Str(String::from("fileA"))), None, false);
+        add_mapping(&mut generator, (1, 0), "fileX", (2, 0), Some("x"));
+        add_mapping(&mut generator, (3, 0), "fileY", (1, 0), None);
+        generator.set_source_content(
+            StringPtr::Str(String::from("fileX")),
+            Some(StringPtr::Str(String::from("lineX1\nlineX2\n"))),
+        );
+        generator.set_source_content(
+            StringPtr::Str(String::from("fileY")),
+            Some(StringPtr::Str(String::from("lineY1\nlineY2\n"))),
+        );
+        generator.to_source_map().unwrap()
+    }
+
+    fn step2() -> SourceMapGenerator {
+        let mut generator = SourceMapGenerator::new(Some(StringPtr::Str(String::from("fileGen"))), None, false);
+        add_mapping(&mut generator, (2, 0), "fileA", (1, 0), None);
+        add_mapping(&mut generator, (3, 0), "fileA", (3, 0), None);
+        add_mapping(&mut generator, (4, 0), "fileB", (1, 0), None);
+        add_mapping(&mut generator, (5, 0), "fileA", (2, 0), None);
+        generator
+    }
+
+    fn add_mapping(
+        generator: &mut SourceMapGenerator,
+        generated: (usize, usize),
+        source: &str,
+        original: (usize, usize),
+        name: Option<&str>,
+    ) {
+        generator.add_mapping(Mapping {
+            generated,
+            source: Some(Ptr::new(String::from(source))),
+            name: name.map(|s| Ptr::new(String::from(s))),
+            original: Some(original),
+            range: false,
+        }).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod write_source_map {
+    use source_map::*;
+
+    fn generator(with_extras: bool) -> SourceMapGenerator {
+        let root = if with_extras { Some(StringPtr::Str(String::from("/src"))) } else { None };
+        let mut generator = SourceMapGenerator::new(Some(StringPtr::Str(String::from("min.js"))), root, false);
+        for &(generated, source, original, name) in &[
+            ((1, 0), "/src/a.js", (1, 0), Some("a")),
+            ((1, 5), "/src/b\"quoted\".js", (3, 2), None),
+            ((4, 1), "/src/a.js", (2, 0), None),
+        ] {
+            generator.add_mapping(Mapping {
+                generated,
+                source: Some(Ptr::new(String::from(source))),
+                name: name.map(|s| Ptr::new(String::from(s))),
+                original: Some(original),
+                range: false,
+            }).unwrap();
+        }
+        if with_extras {
+            generator.set_source_content(
+                StringPtr::Str(String::from("a.js")),
+                Some(StringPtr::Str(String::from("var a;\n\t\"é\"\u{1}"))),
+            );
+            generator.set_source_ignored(StringPtr::Str(String::from("/src/b\"quoted\".js")), true);
+            generator.set_debug_id(Some(StringPtr::Str(String::from("85314830-023f-4cf1-a267-535f4e37bb17"))));
+        }
+        generator
+    }
+
+    #[test]
+    fn matches_to_json() {
+        for &with_extras in &[false, true] {
+            let mut out = Vec::new();
+            generator(with_extras).write_source_map(&mut out).unwrap();
+            let expected = generator(with_extras).to_source_map().unwrap().to_json().unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn reports_invalid_mappings() {
+        let mut generator = generator(false);
+        generator.mappings.add(Mapping {
+            generated: (5, 0),
+            source: Some(Ptr::new(String::from("unknown.js"))),
+            name: None,
+            original: Some((1, 0)),
+            range: false,
+        });
+        match generator.write_source_map(Vec::new()) {
+            Err(Error::InvalidMapping(mapping)) => assert_eq!(mapping.generated, (5, 0)),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod source_root {
+    use source_map::*;
+
+    #[test]
+    fn source_contents_are_keyed_relative_to_the_root() {
+        let mut generator = SourceMapGenerator::new(None, Some(StringPtr::Str(String::from("/the/root"))), false);
+        for (line, source) in ["one.js", "../two.js"].iter().enumerate() {
+            generator.add_mapping(Mapping {
+                generated: (line + 1, 0),
+                source: Some(Ptr::new(String::from(*source))),
+                original: Some((1, 0)),
+                name: None,
+                range: false,
+            }).unwrap();
+        }
+        for &(source, content) in &[("/the/root/one.js", "one"), ("/the/two.js", "two")] {
+            generator.set_source_content(
+                StringPtr::Str(String::from(source)),
+                Some(StringPtr::Str(String::from(content))),
+            );
+        }
+        let map = generator.to_source_map().unwrap();
+        assert_eq!(map.sources, ["one.js", "../two.js"]);
+        assert_eq!(map.sources_content, [Some(String::from("one")), Some(String::from("two"))]);
+    }
+}
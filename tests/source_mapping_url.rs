@@ -0,0 +1,112 @@
+extern crate source_map;
+
+#[cfg(test)]
+mod source_mapping_url {
+    use source_map::*;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    fn output() -> StringWithSrcMap {
+        SourceNode::new(
+            Some((1, 0)),
+            Some(StringPtr::Str(String::from("a.js"))),
+            None,
+            Some(Node::NString(String::from("a { color: red }"))),
+        )
+        .to_string_with_source_map(Some(StringPtr::Str(String::from("a.min.js"))), None)
+        .unwrap()
+    }
+
+    #[test]
+    fn appends_comments() {
+        let mut js = output();
+        js.append_source_mapping_url("a.min.js.map", CommentStyle::Js);
+        assert_eq!(js.source, "a { color: red }\n//# sourceMappingURL=a.min.js.map");
+
+        let mut css = output();
+        css.append_source_mapping_url("a.css.map", CommentStyle::Css);
+        assert_eq!(css.source, "a { color: red }\n/*# sourceMappingURL=a.css.map */");
+        assert_eq!(find_source_mapping_url(&css.source), Some(String::from("a.css.map")));
+    }
+
+    #[test]
+    fn inline_round_trip() {
+        let mut result = output();
+        result.append_inline_source_map(CommentStyle::Js).unwrap();
+        let url = find_source_mapping_url(&result.source).unwrap();
+        assert!(url.starts_with("data:application/json;charset=utf-8;base64,"));
+        assert_eq!(from_data_url(&url).unwrap(), result.map);
+        let loaded = load_source_map(&result.source, Path::new("a.min.js")).unwrap();
+        assert_eq!(loaded, Some(result.map));
+
+        match from_data_url("data:application/json;base64,!!") {
+            Err(Error::InvalidDataUrl) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn percent_encoded_data_url() {
+        let url = concat!(
+            "data:application/json;charset=utf-8,",
+            "%7B%22version%22%3A3%2C%22sources%22%3A%5B%22a%20b.js%22%5D%2C",
+            "%22names%22%3A%5B%5D%2C%22mappings%22%3A%22AAAA%22%7D"
+        );
+        let map = from_data_url(url).unwrap();
+        assert_eq!(map.sources, ["a b.js"]);
+        assert_eq!(map.mappings, "AAAA");
+        let plain = r#"data:application/json,{"version":3,"sources":[],"names":[],"mappings":""}"#;
+        assert_eq!(from_data_url(plain).unwrap().version, 3);
+
+        match from_data_url("data:application/json,%7B%zz") {
+            Err(Error::InvalidDataUrl) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn percent_decodes_file_urls() {
+        let generated = Path::new("/out/a.min.js");
+        assert_eq!(
+            resolve_source_mapping_url("file:///tmp/my%20maps/a%2Bb.map", generated).unwrap(),
+            Path::new("/tmp/my maps/a+b.map")
+        );
+        match resolve_source_mapping_url("file:///tmp/%E0%A4", generated) {
+            Err(Error::UnsupportedUrl(url)) => assert_eq!(url, "file:///tmp/%E0%A4"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn finds_last_comment() {
+        let code = concat!(
+            "//# sourceMappingURL=old.js.map\n",
+            "var s = '//# sourceMappingURL=fake.js.map';\n",
+            "//@ sourceMappingURL=new.js.map\n"
+        );
+        assert_eq!(find_source_mapping_url(code), Some(String::from("new.js.map")));
+        assert_eq!(find_source_mapping_url("var a;"), None);
+    }
+
+    #[test]
+    fn loads_external_map_relative_to_generated_file() {
+        let dir = env::temp_dir().join("source_map_url_test");
+        fs::create_dir_all(dir.join("maps")).unwrap();
+        let result = output();
+        fs::write(dir.join("maps/a.min.js.map"), result.map.to_json().unwrap()).unwrap();
+
+        let code = "a();\n//# sourceMappingURL=maps/a.min.js.map\n";
+        let generated = dir.join("a.min.js");
+        assert_eq!(
+            resolve_source_mapping_url("maps/a.min.js.map", &generated).unwrap(),
+            dir.join("maps/a.min.js.map")
+        );
+        assert_eq!(load_source_map(code, &generated).unwrap(), Some(result.map));
+        assert_eq!(load_source_map("a();", &generated).unwrap(), None);
+        match load_source_map("//# sourceMappingURL=https://example.com/a.map", &generated) {
+            Err(Error::UnsupportedUrl(url)) => assert_eq!(url, "https://example.com/a.map"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
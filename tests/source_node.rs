@@ -1,9 +1,9 @@
+extern crate regex;
 extern crate source_map;
 
 #[cfg(test)]
 mod to_string_with_source_map {
     use source_map::*;
-    use std::rc::Rc;
 
     #[test]
     fn merging_duplicate_mappings() {
@@ -26,7 +26,7 @@ mod to_string_with_source_map {
             ],
         );
         let input =
-            input.to_string_with_source_map(Some(StringPtr::Str(String::from("foo.js"))), None);
+            input.to_string_with_source_map(Some(StringPtr::Str(String::from("foo.js"))), None).unwrap();
 
         assert_eq!(
             input.source,
@@ -56,7 +56,7 @@ mod to_string_with_source_map {
         );
 
         let input_map = input.map;
-        let correct_map = correct_map.to_source_map();
+        let correct_map = correct_map.to_source_map().unwrap();
         assert_eq!(input_map, correct_map);
         assert_eq!(
             input_map.mappings,
@@ -86,7 +86,7 @@ mod to_string_with_source_map {
             ],
         );
         let input =
-            input.to_string_with_source_map(Some(StringPtr::Str(String::from("foo.js"))), None);
+            input.to_string_with_source_map(Some(StringPtr::Str(String::from("foo.js"))), None).unwrap();
 
         assert_eq!(
             input.source,
@@ -125,7 +125,7 @@ mod to_string_with_source_map {
         );
 
         let input_map = input.map;
-        let correct_map = correct_map.to_source_map();
+        let correct_map = correct_map.to_source_map().unwrap();
         assert_eq!(input_map, correct_map);
         assert_eq!(input_map.mappings, "AAAA;AAAA;AAAA;ACCE;AAAA;AAAA;;;;;ACCE");
     }
@@ -138,7 +138,7 @@ mod to_string_with_source_map {
             None,
             Some(Node::NString(String::from(""))),
         );
-        let result = node.to_string_with_source_map(None, None);
+        let result = node.to_string_with_source_map(None, None).unwrap();
         assert_eq!(result.source, "");
     }
 
@@ -154,7 +154,7 @@ mod to_string_with_source_map {
             ],
         );
         let input =
-            input.to_string_with_source_map(Some(StringPtr::Str(String::from("foo.js"))), None);
+            input.to_string_with_source_map(Some(StringPtr::Str(String::from("foo.js"))), None).unwrap();
 
         assert_eq!(
             input.source,
@@ -172,7 +172,7 @@ mod to_string_with_source_map {
         );
 
         let input_map = input.map;
-        let correct_map = correct_map.to_source_map();
+        let correct_map = correct_map.to_source_map().unwrap();
         assert_eq!(input_map, correct_map);
         assert_eq!(input_map.mappings, ";;AAAA;AACA");
     }
@@ -214,13 +214,39 @@ mod to_string_with_source_map {
 
         let map = node
             .to_string_with_source_map(Some(StringPtr::Str(String::from("foo.js"))), None)
+            .unwrap()
             .map;
 
         assert_eq!(map.sources, ["a.js", "b.js"]);
-        assert_eq!(map.sources_content, ["someContent", "otherContent"]);
+        assert_eq!(
+            map.sources_content,
+            [Some(String::from("someContent")), Some(String::from("otherContent"))]
+        );
         assert_eq!(map.mappings, ";EAAC,C,ECAA,C");
     }
 
+    #[test]
+    fn with_ignored_sources() {
+        let mut node = SourceNode::new(None, None, None, None);
+        add_sources_by_params(
+            &mut node,
+            &[
+                (1, 0, "app.js", "app();\n", None),
+                (1, 0, "node_modules/lib.js", "lib();\n", None),
+                (1, 0, "vendor.js", "vendor();\n", None),
+            ],
+        );
+        node.set_source_ignored(StringPtr::Str(String::from("vendor.js")), true);
+
+        let map = node.to_string_with_source_map(None, None).unwrap().map;
+        assert_eq!(map.sources, ["app.js", "node_modules/lib.js", "vendor.js"]);
+        assert_eq!(map.ignore_list, [2]);
+
+        let mut generator = node.to_source_map_generator(None, None);
+        generator.ignore_sources(|source| source.starts_with("node_modules/"));
+        assert_eq!(generator.to_source_map().unwrap().ignore_list, [1, 2]);
+    }
+
     fn add_sources_by_params(sn: &mut SourceNode, params: &[(i32, i32, &str, &str, Option<&str>)]) {
         for param in params {
             let line = param.0;
@@ -241,25 +267,334 @@ mod to_string_with_source_map {
         }
     }
 
+    #[allow(clippy::type_complexity)]
     fn add_mappings_by_params(
         smg: &mut SourceMapGenerator,
         params: &[(i32, i32, Option<&str>, i32, i32, Option<&str>)],
     ) {
         for param in params {
             let generated = (param.0 as usize, param.1 as usize);
-            let source = param.2.map(|s| Rc::new(String::from(s)));
+            let source = param.2.map(|s| Ptr::new(String::from(s)));
             let original = if param.3 >= 0 {
                 Some((param.3 as usize, param.4 as usize))
             } else {
                 None
             };
-            let name = param.5.map(|s| Rc::new(String::from(s)));
+            let name = param.5.map(|s| Ptr::new(String::from(s)));
             smg.add_mapping(Mapping {
                 generated,
                 source,
                 original,
                 name,
-            });
+                range: false,
+            }).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod manipulation {
+    use regex::Regex;
+    use source_map::*;
+
+    fn chunk(line: usize, source: &str, code: &str) -> Node {
+        Node::NSourceNode(SourceNode::new(
+            Some((line, 0)),
+            Some(StringPtr::Str(String::from(source))),
+            None,
+            Some(Node::NString(String::from(code))),
+        ))
+    }
+
+    #[test]
+    fn prepend() {
+        let mut node = SourceNode::new(None, None, None, Some(chunk(1, "a.js", "body();")));
+        node.prepend(Node::NNodeVec(vec![
+            Node::NString(String::from("(function () {")),
+            Node::NString(String::from("\n")),
+        ]));
+        node.prepend(chunk(1, "header.js", "/* header */"));
+        node.add(Node::NString(String::from("\n}());")));
+
+        let result = node.to_string_with_source_map(None, None).unwrap();
+        assert_eq!(result.source, "/* header */(function () {\nbody();\n}());");
+        assert_eq!(result.map.sources, ["header.js", "a.js"]);
+        assert_eq!(result.map.mappings, "AAAA,Y;ACAA,O");
+    }
+
+    #[test]
+    fn add_flattens_nested_chunk_lists() {
+        let mut node = SourceNode::new(Some((1, 0)), Some(StringPtr::Str(String::from("a.js"))), None, None);
+        node.add(Node::NNodeVec(vec![
+            Node::NString(String::from("a")),
+            Node::NNodeVec(vec![Node::NString(String::from("b")), chunk(2, "a.js", "c")]),
+        ]));
+        assert_eq!(node.children.len(), 3);
+
+        let mut walked = Vec::new();
+        node.walk(|chunk, _, position, _| walked.push((String::from(chunk), position)));
+        assert_eq!(
+            walked,
+            [
+                (String::from("a"), Some((1, 0))),
+                (String::from("b"), Some((1, 0))),
+                (String::from("c"), Some((2, 0))),
+            ]
+        );
+        assert_eq!(node.to_string(), "abc");
+    }
+
+    #[test]
+    fn join_leaves_separators_unmapped() {
+        let mut node = SourceNode::new(
+            Some((1, 0)),
+            Some(StringPtr::Str(String::from("args.js"))),
+            None,
+            Some(Node::NNodeVec(vec![chunk(1, "a.js", "a"), chunk(2, "a.js", "b"), chunk(3, "a.js", "c")])),
+        );
+        node.join(StringPtr::Str(String::from(", ")));
+
+        let result = node.to_string_with_source_map(None, None).unwrap();
+        assert_eq!(result.source, "a, b, c");
+        assert_eq!(result.map.mappings, "AAAA,C,EACA,C,EACA");
+
+        let mut empty = SourceNode::new(None, None, None, None);
+        empty.join(StringPtr::Str(String::from(", ")));
+        assert!(empty.children.is_empty());
+    }
+
+    #[test]
+    fn replace_right_edits_last_nested_chunk() {
+        let mut inner = SourceNode::new(None, None, None, Some(chunk(1, "a.js", "a();")));
+        inner.add(chunk(2, "a.js", "b();  "));
+        let mut node = SourceNode::new(None, None, None, Some(Node::NString(String::from("x;"))));
+        node.add(Node::NSourceNode(inner));
+
+        node.replace_right(&Regex::new(r";\s*$").unwrap(), "");
+        let result = node.to_string_with_source_map(None, None).unwrap();
+        assert_eq!(result.source, "x;a();b()");
+        assert_eq!(result.map.mappings, "EAAA,IACA");
+
+        let mut plain = SourceNode::new(None, None, None, Some(Node::NString(String::from("a;;"))));
+        plain.replace_right(&Regex::new(";").unwrap(), "");
+        assert_eq!(plain.to_string_with_source_map(None, None).unwrap().source, "a;");
+    }
+}
+
+#[cfg(test)]
+mod visitor {
+    use source_map::*;
+
+    fn node() -> SourceNode {
+        let mut inner = SourceNode::new(
+            Some((2, 4)),
+            Some(StringPtr::Str(String::from("b.js"))),
+            Some(StringPtr::Str(String::from("b"))),
+            Some(Node::NString(String::from("b()"))),
+        );
+        inner.set_source_content(StringPtr::Str(String::from("b.js")), StringPtr::Str(String::from("b content")));
+        let mut node = SourceNode::new(
+            Some((1, 0)),
+            Some(StringPtr::Str(String::from("a.js"))),
+            None,
+            Some(Node::NString(String::from("a("))),
+        );
+        node.add(Node::NSourceNode(inner));
+        node.add(Node::NString(String::from(");\n")));
+        node.set_source_content(StringPtr::Str(String::from("a.js")), StringPtr::Str(String::from("a content")));
+        node
+    }
+
+    #[test]
+    fn walk() {
+        let mut chunks = Vec::new();
+        node().walk(|chunk, source, position, name| {
+            chunks.push((
+                String::from(chunk),
+                source.map(|s| s.to_string()),
+                position,
+                name.map(|s| s.to_string()),
+            ))
+        });
+        let a = Some(String::from("a.js"));
+        assert_eq!(
+            chunks,
+            [
+                (String::from("a("), a.clone(), Some((1, 0)), None),
+                (String::from("b()"), Some(String::from("b.js")), Some((2, 4)), Some(String::from("b"))),
+                (String::from(");\n"), a, Some((1, 0)), None),
+            ]
+        );
+
+        let mut contents = Vec::new();
+        node().walk_source_contents(|source, content| contents.push((source.to_string(), content.to_string())));
+        assert_eq!(
+            contents,
+            [
+                (String::from("b.js"), String::from("b content")),
+                (String::from("a.js"), String::from("a content")),
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_visitor() {
+        #[derive(Default)]
+        struct Size {
+            mapped: usize,
+            unmapped: usize,
+            sources: usize,
         }
+        impl SourceNodeVisitor for Size {
+            fn visit_chunk(&mut self, chunk: &str, source: Option<&Ptr<String>>, _: Option<(usize, usize)>, _: Option<&Ptr<String>>) {
+                if source.is_some() {
+                    self.mapped += chunk.len();
+                } else {
+                    self.unmapped += chunk.len();
+                }
+            }
+
+            fn visit_source_content(&mut self, _: &Ptr<String>, _: &Ptr<String>) {
+                self.sources += 1;
+            }
+        }
+
+        let mut node = node();
+        node.add(Node::NString(String::from("//")));
+        let mut wrapper = SourceNode::new(None, None, None, Some(Node::NSourceNode(node)));
+        wrapper.add(Node::NString(String::from("end")));
+        let mut size = Size::default();
+        wrapper.visit(&mut size);
+        assert_eq!((size.mapped, size.unmapped, size.sources), (10, 3, 2));
+    }
+
+    #[test]
+    fn display() {
+        let node = node();
+        assert_eq!(node.to_string(), "a(b());\n");
+        assert_eq!(node.to_string(), node.to_string_with_source_map(None, None).unwrap().source);
+        assert_eq!(format!("[{}]", SourceNode::new(None, None, None, None)), "[]");
+    }
+}
+
+#[cfg(test)]
+mod from_string_with_source_map {
+    use source_map::*;
+
+    fn consumer() -> SourceMapConsumer {
+        SourceMapConsumer::from_json(
+            r#"{
+                "version": 3,
+                "sourceRoot": "src",
+                "sources": ["a.js", "b.js"],
+                "sourcesContent": ["a()", null],
+                "names": [],
+                "mappings": "AAAA,IACA;ACAA",
+                "ignoreList": [1]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn round_trips_code() {
+        let code = "one two\nthree";
+        let node = SourceNode::from_string_with_source_map(code, &consumer(), None);
+        let result = node.to_string_with_source_map(None, None).unwrap();
+        assert_eq!(result.source, code);
+        assert_eq!(result.map.sources, vec!["src/a.js", "src/b.js"]);
+        assert_eq!(result.map.mappings, "AAAA,IACA;ACAA,K");
+    }
+
+    #[test]
+    fn resolves_sources_against_relative_path() {
+        let node = SourceNode::from_string_with_source_map("one two\nthree", &consumer(), Some("lib/"));
+        let result = node.to_string_with_source_map(None, None).unwrap();
+        assert_eq!(result.map.sources, vec!["lib/src/a.js", "lib/src/b.js"]);
+        assert_eq!(result.map.sources_content, vec![Some(String::from("a()")), None]);
+        assert_eq!(result.map.ignore_list, vec![1]);
+    }
+
+    #[test]
+    fn from_src_map() {
+        let src_map = SrcMap {
+            version: 3,
+            sources: vec![String::from("a.js")],
+            mappings: String::from("AAAA"),
+            ..SrcMap::default()
+        };
+        let node = SourceNode::from_string_with_src_map("x", src_map, Some("/root")).unwrap();
+        let result = node.to_string_with_source_map(None, None).unwrap();
+        assert_eq!(result.map.sources, vec!["/root/a.js"]);
+    }
+}
+
+#[cfg(test)]
+mod builder {
+    use source_map::*;
+
+    #[test]
+    fn macro_matches_manual_tree() {
+        let built = source_node![
+            source_node![(1, 0, "a.js") => "function ", source_node![(1, 9, "a.js", "add") => "add"], "() {\n"],
+            "  ",
+            source_node![(2, 2, "a.js") => vec!["return ", "1;"]],
+            String::from("\n}"),
+        ];
+
+        let mut manual = SourceNode::new(None, None, None, None);
+        let mut function = SourceNode::new(Some((1, 0)), Some(StringPtr::Str(String::from("a.js"))), None, None);
+        function.add(Node::NString(String::from("function ")));
+        function.add(Node::NSourceNode(SourceNode::new(
+            Some((1, 9)),
+            Some(StringPtr::Str(String::from("a.js"))),
+            Some(StringPtr::Str(String::from("add"))),
+            Some(Node::NString(String::from("add"))),
+        )));
+        function.add(Node::NString(String::from("() {\n")));
+        manual.add(Node::NSourceNode(function));
+        manual.add(Node::NString(String::from("  ")));
+        manual.add(Node::NSourceNode(SourceNode::new(
+            Some((2, 2)),
+            Some(StringPtr::Str(String::from("a.js"))),
+            None,
+            Some(Node::NString(String::from("return 1;"))),
+        )));
+        manual.add(Node::NString(String::from("\n}")));
+
+        let built = built.to_string_with_source_map(None, None).unwrap();
+        let manual = manual.to_string_with_source_map(None, None).unwrap();
+        assert_eq!(built.source, "function add() {\n  return 1;\n}");
+        assert_eq!(built.source, manual.source);
+        assert_eq!(built.map.mappings, manual.map.mappings);
+        assert_eq!(built.map.names, vec!["add"]);
+    }
+
+    #[test]
+    fn conversions() {
+        let shared = Ptr::new(String::from("b"));
+        let mut node = SourceNode::at(1, 0, "a.js").child("a").child(shared);
+        node.add(vec![String::from("c"), String::from("d")]);
+        node.prepend(SourceNode::at(2, 0, "a.js").child(String::from("z")));
+        node.join(", ");
+        node.set_source_content("a.js", "a b c d");
+        node.set_source_ignored(String::from("a.js"), true);
+        let result = node.to_string_with_source_map(None, None).unwrap();
+        assert_eq!(result.source, "z, a, b, c, d");
+        assert_eq!(result.map.sources_content, vec![Some(String::from("a b c d"))]);
+        assert_eq!(result.map.ignore_list, vec![0]);
+
+        let node = SourceNode::new((1, 2), "a.js", "x", "x");
+        let expected = SourceNode::at(1, 2, "a.js").named("x").child("x");
+        assert_eq!(
+            node.to_string_with_source_map(None, None).unwrap(),
+            expected.to_string_with_source_map(None, None).unwrap()
+        );
+        let node = SourceNode::new((1, 2), String::from("a.js"), None, Ptr::new(String::from("x")));
+        assert_eq!(node.source.as_ref().map(|source| source.as_str()), Some("a.js"));
+        assert_eq!(node.name, None);
+        assert_eq!(node.to_string(), "x");
+        let trailing = source_node![(1, 2, "a.js", "x") => "x",];
+        assert_eq!(trailing.to_string(), "x");
     }
 }
@@ -0,0 +1,43 @@
+#![cfg(feature = "sync")]
+extern crate source_map;
+
+#[cfg(test)]
+mod sync {
+    use source_map::*;
+    use std::thread;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn types_are_send_and_sync() {
+        assert_send_sync::<SourceNode>();
+        assert_send_sync::<Mapping>();
+        assert_send_sync::<SourceMapGenerator>();
+        assert_send_sync::<SrcMap>();
+    }
+
+    #[test]
+    fn combines_nodes_built_on_threads() {
+        let handles: Vec<_> = (1..4)
+            .map(|i| {
+                thread::spawn(move || {
+                    SourceNode::new(
+                        Some((i, 0)),
+                        Some(StringPtr::Str(format!("{}.js", i))),
+                        None,
+                        Some(Node::NString(format!("chunk{};\n", i))),
+                    )
+                })
+            })
+            .collect();
+        let chunks = handles
+            .into_iter()
+            .map(|handle| Node::NSourceNode(handle.join().unwrap()))
+            .collect();
+        let node = SourceNode::new(None, None, None, Some(Node::NNodeVec(chunks)));
+
+        let result = node.to_string_with_source_map(None, None).unwrap();
+        assert_eq!(result.source, "chunk1;\nchunk2;\nchunk3;\n");
+        assert_eq!(result.map.sources, ["1.js", "2.js", "3.js"]);
+    }
+}